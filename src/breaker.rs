@@ -0,0 +1,180 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    /// Calls go through; counts the failures in a row.
+    Closed { failures: u32 },
+    /// Calls fail fast until the cooldown is over.
+    Open { until: Instant },
+    /// The cooldown is over and a single trial call is in flight.
+    HalfOpen { until: Instant },
+}
+
+/// Stops sending calls to a node after `threshold` consecutive failures, then lets a
+/// single trial call through every `cooldown` until one succeeds.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    /// A `threshold` of 0 disables the breaker.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may be made now.
+    pub fn allow(&self) -> bool {
+        if self.threshold == 0 {
+            return true;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        match *state {
+            State::Closed { .. } => true,
+            // A trial whose caller went away never reports back, so allow another one
+            // once it has been pending for a whole cooldown.
+            State::Open { until } | State::HalfOpen { until } if now >= until => {
+                *state = State::HalfOpen { until: now + self.cooldown };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Whether calls are currently being refused, without claiming a trial call.
+    pub fn is_open(&self) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => false,
+            State::Open { until } | State::HalfOpen { until } => Instant::now() < until,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let until = Instant::now() + self.cooldown;
+
+        *state = match *state {
+            State::Closed { failures } if failures + 1 < self.threshold => State::Closed { failures: failures + 1 },
+            State::Closed { .. } | State::HalfOpen { .. } => {
+                tracing::warn!(cooldown = self.cooldown.as_secs(), "upstream node keeps failing, opening circuit");
+                State::Open { until }
+            }
+            open @ State::Open { .. } => open,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        assert!(breaker.allow());
+        assert!(!breaker.is_open());
+
+        breaker.record_failure();
+        assert!(!breaker.allow());
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn lets_a_single_trial_through_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record_failure();
+        assert!(!breaker.allow());
+
+        thread::sleep(Duration::from_millis(30));
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn closes_when_the_trial_succeeds() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record_failure();
+        thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+
+        breaker.record_success();
+        assert!(breaker.allow());
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn reopens_when_the_trial_fails() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record_failure();
+        thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+
+        breaker.record_failure();
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn allows_another_trial_when_one_never_reports_back() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record_failure();
+        thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+
+        thread::sleep(Duration::from_millis(30));
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn zero_threshold_never_opens() {
+        let breaker = CircuitBreaker::new(0, Duration::from_secs(60));
+
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+
+        assert!(breaker.allow());
+        assert!(!breaker.is_open());
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, RwLock};
+use std::time::{Duration, Instant};
+
+use penumbra_proto::core::app::v1::AppParameters;
+use penumbra_proto::core::component::governance::v1::ProposalListResponse;
+use penumbra_stake::{validator, IdentityKey, Uptime};
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::tokio::{self, sync::Mutex};
+use tracing::Instrument;
+
+use crate::config::CacheConfig;
+use crate::clock::ChainClock;
+use crate::error::Error;
+use crate::metrics;
+
+const BLOCK_TIME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How a response was served with regard to the cache, reported in the `X-Cache` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Served from a fresh cache entry.
+    Hit,
+    /// Served from an expired entry while it is being refreshed in the background.
+    Stale,
+    /// Fetched from the node and stored.
+    Miss,
+    /// Caching is disabled for this query.
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Stale => "STALE",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+
+    /// Status of a response built from several lookups: a hit only if all of them were.
+    pub fn merge(self, other: CacheStatus) -> CacheStatus {
+        use CacheStatus::*;
+
+        match (self, other) {
+            (Miss, _) | (_, Miss) => Miss,
+            (Bypass, _) | (_, Bypass) => Bypass,
+            (Stale, _) | (_, Stale) => Stale,
+            (Hit, Hit) => Hit,
+        }
+    }
+}
+
+struct Entry<T> {
+    value: Arc<T>,
+    fetched_at: Instant,
+}
+
+/// Cached result of a single upstream query.
+pub struct Slot<T> {
+    name: &'static str,
+    ttl: Duration,
+    stale_ttl: Duration,
+    entry: RwLock<Option<Entry<T>>>,
+    // Serializes misses so that concurrent requests result in one upstream call.
+    fill: Mutex<()>,
+    refreshing: AtomicBool,
+}
+
+impl<T: Send + Sync + 'static> Slot<T> {
+    fn new(name: &'static str, ttl: Duration, stale_ttl: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            ttl,
+            stale_ttl,
+            entry: RwLock::new(None),
+            fill: Mutex::new(()),
+            refreshing: AtomicBool::new(false),
+        })
+    }
+
+    fn lookup(&self) -> Option<(Arc<T>, Duration)> {
+        let entry = self.entry.read().unwrap();
+        entry.as_ref().map(|entry| (entry.value.clone(), entry.fetched_at.elapsed()))
+    }
+
+    fn store(&self, value: Arc<T>) {
+        *self.entry.write().unwrap() = Some(Entry {
+            value,
+            fetched_at: Instant::now(),
+        });
+    }
+
+    /// Returns the cached value, fetching it with `fetch` when it is missing or too old.
+    ///
+    /// Entries older than the TTL but still within the stale window are returned as is
+    /// while a single background task refreshes them.
+    pub async fn get<F, Fut>(self: &Arc<Self>, fetch: F) -> Result<(Arc<T>, CacheStatus), Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Error>> + Send + 'static,
+    {
+        let result = self.lookup_or_fetch(fetch).await;
+        if let Ok((_, status)) = &result {
+            metrics::record_cache_lookup(self.name, status.as_str());
+        }
+
+        result
+    }
+
+    async fn lookup_or_fetch<F, Fut>(self: &Arc<Self>, fetch: F) -> Result<(Arc<T>, CacheStatus), Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Error>> + Send + 'static,
+    {
+        if self.ttl.is_zero() {
+            return Ok((Arc::new(fetch().await?), CacheStatus::Bypass));
+        }
+
+        if let Some((value, age)) = self.lookup() {
+            if age < self.ttl {
+                return Ok((value, CacheStatus::Hit));
+            }
+
+            if age < self.ttl + self.stale_ttl {
+                if !self.refreshing.swap(true, Ordering::SeqCst) {
+                    let slot = self.clone();
+                    let refresh = fetch();
+                    tokio::spawn(async move {
+                        if let Ok(value) = refresh.await {
+                            slot.store(Arc::new(value));
+                        }
+                        slot.refreshing.store(false, Ordering::SeqCst);
+                    }.in_current_span());
+                }
+
+                return Ok((value, CacheStatus::Stale));
+            }
+        }
+
+        let _guard = self.fill.lock().await;
+
+        // Another request may have filled the slot while we were waiting for the lock.
+        if let Some((value, age)) = self.lookup() {
+            if age < self.ttl {
+                return Ok((value, CacheStatus::Hit));
+            }
+        }
+
+        let value = Arc::new(fetch().await?);
+        self.store(value.clone());
+
+        Ok((value, CacheStatus::Miss))
+    }
+}
+
+/// One slot per key, for queries taking a parameter. Slots are never evicted, so keys
+/// must come from a bounded set such as the validators.
+pub struct Keyed<K, T> {
+    name: &'static str,
+    ttl: Duration,
+    stale_ttl: Duration,
+    slots: Arc<StdMutex<HashMap<K, Arc<Slot<T>>>>>,
+}
+
+impl<K, T> Clone for Keyed<K, T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            ttl: self.ttl,
+            stale_ttl: self.stale_ttl,
+            slots: self.slots.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, T: Send + Sync + 'static> Keyed<K, T> {
+    fn new(name: &'static str, ttl: Duration, stale_ttl: Duration) -> Self {
+        Self {
+            name,
+            ttl,
+            stale_ttl,
+            slots: Arc::new(StdMutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn slot(&self, key: K) -> Arc<Slot<T>> {
+        self.slots
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Slot::new(self.name, self.ttl, self.stale_ttl))
+            .clone()
+    }
+}
+
+/// In-process cache of the upstream queries that only change per block or per epoch.
+/// Clones share the entries.
+#[derive(Clone)]
+pub struct Cache {
+    pub validator_info: Arc<Slot<Vec<validator::Info>>>,
+    pub app_parameters: Arc<Slot<AppParameters>>,
+    pub proposal_list: Arc<Slot<Vec<ProposalListResponse>>>,
+    pub bond_denom: Arc<Slot<String>>,
+    pub chain_clock: Arc<Slot<ChainClock>>,
+    /// Index of the current epoch, which validator rates are for.
+    pub current_epoch: Arc<Slot<u64>>,
+    pub validator_uptime: Keyed<IdentityKey, Uptime>,
+    /// Block times by height; these never change, so they are kept for a long time.
+    pub block_time: Keyed<u64, i64>,
+}
+
+impl Cache {
+    pub fn new(config: &CacheConfig) -> Self {
+        let stale_ttl = Duration::from_secs(config.stale_ttl);
+
+        Self {
+            validator_info: Slot::new("validator_info", Duration::from_secs(config.validators_ttl), stale_ttl),
+            app_parameters: Slot::new("app_parameters", Duration::from_secs(config.params_ttl), stale_ttl),
+            proposal_list: Slot::new("proposal_list", Duration::from_secs(config.proposals_ttl), stale_ttl),
+            bond_denom: Slot::new("bond_denom", Duration::from_secs(config.params_ttl), stale_ttl),
+            chain_clock: Slot::new("chain_clock", Duration::from_secs(config.clock_ttl), stale_ttl),
+            current_epoch: Slot::new("current_epoch", Duration::from_secs(config.validators_ttl), stale_ttl),
+            validator_uptime: Keyed::new("validator_uptime", Duration::from_secs(config.uptime_ttl), stale_ttl),
+            block_time: Keyed::new("block_time", BLOCK_TIME_TTL, Duration::ZERO),
+        }
+    }
+}
+
+/// Wraps a response with the `X-Cache` header describing how it was served.
+pub struct Cached<R>(pub R, pub CacheStatus);
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Cached<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        Response::build_from(self.0.respond_to(request)?)
+            .header(Header::new("X-Cache", self.1.as_str()))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use futures::future::{join_all, BoxFuture};
+
+    use super::*;
+
+    /// A fetch answering `value` after `delay`, counting how many times it ran.
+    fn fetch(calls: &Arc<AtomicUsize>, value: u64, delay: Duration) -> impl FnOnce() -> BoxFuture<'static, Result<u64, Error>> {
+        let calls = calls.clone();
+        move || Box::pin(async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(delay).await;
+            Ok(value)
+        })
+    }
+
+    fn slot(ttl: Duration) -> Arc<Slot<u64>> {
+        Slot::new("test", ttl, Duration::from_secs(60))
+    }
+
+    #[rocket::async_test]
+    async fn fresh_entries_are_hits() {
+        let (slot, calls) = (slot(Duration::from_secs(60)), Arc::new(AtomicUsize::new(0)));
+
+        let (value, status) = slot.lookup_or_fetch(fetch(&calls, 1, Duration::ZERO)).await.unwrap();
+        assert_eq!((*value, status), (1, CacheStatus::Miss));
+
+        let (value, status) = slot.lookup_or_fetch(fetch(&calls, 2, Duration::ZERO)).await.unwrap();
+        assert_eq!((*value, status), (1, CacheStatus::Hit));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[rocket::async_test]
+    async fn stale_entries_are_refreshed_once_in_the_background() {
+        let (slot, calls) = (slot(Duration::from_secs(1)), Arc::new(AtomicUsize::new(0)));
+        *slot.entry.write().unwrap() = Some(Entry {
+            value: Arc::new(1),
+            fetched_at: Instant::now() - Duration::from_secs(2),
+        });
+
+        for _ in 0..3 {
+            let (value, status) = slot.lookup_or_fetch(fetch(&calls, 2, Duration::from_millis(50))).await.unwrap();
+            assert_eq!((*value, status), (1, CacheStatus::Stale));
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let (value, status) = slot.lookup_or_fetch(fetch(&calls, 3, Duration::ZERO)).await.unwrap();
+        assert_eq!((*value, status), (2, CacheStatus::Hit));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[rocket::async_test]
+    async fn concurrent_misses_fetch_once() {
+        let (slot, calls) = (slot(Duration::from_secs(60)), Arc::new(AtomicUsize::new(0)));
+
+        let lookups = (0..5).map(|value| slot.lookup_or_fetch(fetch(&calls, value, Duration::from_millis(50))));
+        let results = join_all(lookups).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let statuses: Vec<_> = results.into_iter().map(|result| result.unwrap().1).collect();
+        assert_eq!(statuses.iter().filter(|status| **status == CacheStatus::Miss).count(), 1);
+        assert_eq!(statuses.iter().filter(|status| **status == CacheStatus::Hit).count(), 4);
+    }
+
+    #[rocket::async_test]
+    async fn zero_ttl_bypasses_the_cache() {
+        let (slot, calls) = (slot(Duration::ZERO), Arc::new(AtomicUsize::new(0)));
+
+        for value in 0..2 {
+            let (fetched, status) = slot.lookup_or_fetch(fetch(&calls, value, Duration::ZERO)).await.unwrap();
+            assert_eq!((*fetched, status), (value, CacheStatus::Bypass));
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(slot.lookup().is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use penumbra_proto::util::tendermint_proxy::v1::{
+    GetBlockByHeightRequest,
+    GetBlockByHeightResponse,
+    GetStatusRequest,
+    GetStatusResponse,
+    SyncInfo,
+};
+
+use crate::cache::{Cache, CacheStatus};
+use crate::error::Error;
+use crate::metrics;
+use crate::upstream::Upstream;
+
+/// Latest block and average block time, to turn block heights into timestamps.
+pub struct ChainClock {
+    pub latest_block_height: u64,
+    pub latest_block_time: i64,
+    /// Average time between blocks, in seconds.
+    pub block_time: f64,
+}
+
+impl ChainClock {
+    pub fn estimate(&self, height: u64) -> Option<DateTime<Utc>> {
+        let blocks = height as f64 - self.latest_block_height as f64;
+        DateTime::from_timestamp(self.latest_block_time + (blocks * self.block_time) as i64, 0)
+    }
+}
+
+/// The chain clock, cached for `cache.clock_ttl` seconds.
+pub async fn get_chain_clock(upstream: &Upstream, cache: &Cache) -> Result<(Arc<ChainClock>, CacheStatus), Error> {
+    let upstream = upstream.clone();
+
+    cache.chain_clock.get(|| async move {
+        let sync_info = get_sync_info(&upstream).await?;
+        let latest_block_time = sync_info
+            .latest_block_time
+            .ok_or_else(|| Error::missing("latest_block_time"))?
+            .seconds;
+        let block_time = get_block_time(&upstream, sync_info.latest_block_height as i64, latest_block_time as f64).await?;
+
+        Ok(ChainClock {
+            latest_block_height: sync_info.latest_block_height,
+            latest_block_time,
+            block_time,
+        })
+    }).await
+}
+
+/// Sync state of the node that answered.
+pub async fn get_sync_info(upstream: &Upstream) -> Result<SyncInfo, Error> {
+    upstream
+        .call(|upstream| async move {
+            let status_data: GetStatusResponse = upstream
+                .tendermint()
+                .get_status(GetStatusRequest { })
+                .await?
+                .into_inner();
+
+            let sync_info = status_data.sync_info.ok_or_else(|| Error::missing("sync_info"))?;
+            // Within the call, `current` is the node this attempt went to.
+            metrics::record_latest_height(&upstream.current().url, sync_info.latest_block_height);
+
+            Ok(sync_info)
+        })
+        .await
+}
+
+/// Average block time over the last 100 blocks, or since the first block on younger chains.
+async fn get_block_time(upstream: &Upstream, latest_block_height: i64, latest_block_time: f64) -> Result<f64, Error> {
+    if latest_block_height < 2 {
+        return Err(Error::decode("not enough blocks to measure the block time"));
+    }
+
+    let older_height = (latest_block_height - 100).max(1);
+    let older_block_data: GetBlockByHeightResponse = upstream
+        .call(|upstream| async move {
+            Ok(upstream
+                .tendermint()
+                .get_block_by_height(GetBlockByHeightRequest { height: older_height })
+                .await?)
+        })
+        .await?
+        .into_inner();
+
+    let older_block_header = older_block_data
+        .block
+        .and_then(|block| block.header)
+        .ok_or_else(|| Error::missing("block header"))?;
+    let older_block_height = older_block_header.height;
+    let older_block_time: f64 = older_block_header
+        .time
+        .ok_or_else(|| Error::missing("block time"))?
+        .seconds as f64;
+
+    let time_between_blocks = latest_block_time - older_block_time;
+    let blocks_diff = latest_block_height - older_block_height;
+    if blocks_diff <= 0 {
+        return Err(Error::decode(format!("block {} is not older than block {}", older_block_height, latest_block_height)));
+    }
+
+    Ok(time_between_blocks / (blocks_diff as f64))
+}
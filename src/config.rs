@@ -0,0 +1,592 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+use clap::Parser;
+use rocket::figment::providers::{Env, Format, Serialized, Toml};
+use rocket::figment::Figment;
+use rocket::serde::{Deserialize, Serialize};
+
+/// Configuration file looked up in the working directory (and its parents) when `--config` is not given.
+const DEFAULT_CONFIG_FILE: &str = "penumbra-lcd.toml";
+
+/// Command line flags. Every flag is optional and overrides the configuration file and
+/// `LCD_`-prefixed environment variables, see [`Config`].
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// TOML configuration file
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// pd node gRPC URL; repeat or separate with commas to fail over between several nodes
+    #[arg(short, long, value_delimiter = ',')]
+    node: Vec<String>,
+
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    #[arg(short, long)]
+    bind: Option<IpAddr>,
+
+    /// Timeout for establishing a connection to the node, in seconds
+    #[arg(long)]
+    connect_timeout: Option<u64>,
+
+    /// Interval between TCP and HTTP/2 keepalive pings to the node, in seconds
+    #[arg(long)]
+    keepalive_interval: Option<u64>,
+
+    /// Time to wait for a keepalive ping acknowledgement before dropping the connection, in seconds
+    #[arg(long)]
+    keepalive_timeout: Option<u64>,
+
+    /// PEM bundle of CA certificates trusted for https:// nodes, in addition to the public roots
+    #[arg(long)]
+    node_ca_cert: Option<String>,
+
+    /// PEM client certificate presented to nodes requiring mutual TLS
+    #[arg(long, requires = "node_client_key")]
+    node_client_cert: Option<String>,
+
+    /// PEM private key for --node-client-cert
+    #[arg(long, requires = "node_client_cert")]
+    node_client_key: Option<String>,
+
+    /// Server name used for SNI and certificate verification instead of the node URL host
+    #[arg(long)]
+    node_tls_domain: Option<String>,
+
+    /// Do not verify node certificates; only meant for testing
+    #[arg(long)]
+    node_insecure_skip_verify: bool,
+
+    /// PEM certificate chain to serve HTTPS with
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<String>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<String>,
+
+    /// PEM bundle of CA certificates; when set, clients must present a certificate signed by one of them
+    #[arg(long, requires = "tls_cert")]
+    tls_client_ca: Option<String>,
+
+    /// How often to check the TLS certificate files for changes and reload them, in seconds (0 disables).
+    /// A reload restarts the listener: connections are refused until it binds again, and the
+    /// shutdown grace is capped at 2 seconds so that this stays short
+    #[arg(long)]
+    tls_reload_interval: Option<u64>,
+
+    /// Deadline for a single upstream call, including reading streamed responses, in seconds
+    #[arg(long)]
+    call_timeout: Option<u64>,
+
+    /// How many times an upstream call failing with Unavailable or DeadlineExceeded is retried
+    #[arg(long)]
+    retries: Option<u32>,
+
+    /// Consecutive failed calls after which a node is not queried for --breaker-cooldown (0 disables)
+    #[arg(long)]
+    breaker_threshold: Option<u32>,
+
+    /// How long a failing node is left alone before a trial call, in seconds
+    #[arg(long)]
+    breaker_cooldown: Option<u64>,
+
+    /// Interval between node health checks, in seconds
+    #[arg(long)]
+    health_check_interval: Option<u64>,
+
+    /// How many blocks a node may lag behind the most synced one and still receive queries
+    #[arg(long)]
+    max_height_lag: Option<u64>,
+
+    /// Age of the latest block, in seconds, past which /ready reports the instance as not ready
+    #[arg(long)]
+    max_block_age: Option<u64>,
+
+    /// How long the validator list is cached, in seconds (0 disables caching)
+    #[arg(long)]
+    validators_ttl: Option<u64>,
+
+    /// How long app parameters are cached, in seconds (0 disables caching)
+    #[arg(long)]
+    params_ttl: Option<u64>,
+
+    /// How long the proposal list is cached, in seconds (0 disables caching)
+    #[arg(long)]
+    proposals_ttl: Option<u64>,
+
+    /// How long validator uptimes are cached, in seconds (0 disables caching)
+    #[arg(long)]
+    uptime_ttl: Option<u64>,
+
+    /// How many validator uptimes signing_infos fetches at once
+    #[arg(long)]
+    uptime_concurrency: Option<usize>,
+
+    /// How long an expired cache entry may still be served while it is being refreshed, in seconds
+    #[arg(long)]
+    cache_stale_ttl: Option<u64>,
+
+    /// Requests per second each client IP may make on average (0 disables rate limiting)
+    #[arg(long)]
+    rate_limit: Option<f64>,
+
+    /// Requests a client IP may make in a burst above --rate-limit
+    #[arg(long)]
+    rate_limit_burst: Option<f64>,
+
+    /// Proxy address or network whose X-Forwarded-For header is trusted; repeat or separate with commas
+    #[arg(long, value_delimiter = ',')]
+    trusted_proxy: Vec<String>,
+
+    /// Maximum number of upstream calls in flight at once, across all clients (0 is unlimited)
+    #[arg(long)]
+    max_upstream_calls: Option<usize>,
+
+    /// Origin allowed to make cross-origin requests, or * for any; repeat or separate with commas
+    #[arg(long, value_delimiter = ',')]
+    cors_origin: Vec<String>,
+
+    /// Log line format
+    #[arg(long, value_enum)]
+    log_format: Option<LogFormat>,
+
+    /// Log filter, either a level (`debug`) or per-target directives (`info,penumbra_lcd=debug`)
+    #[arg(long)]
+    log_level: Option<String>,
+}
+
+/// Settings of the LCD itself. Rocket's own settings (`port`, `address`, `tls`, ...) live
+/// in the same figment, so a single file configures both:
+///
+/// ```toml
+/// port = 1317
+/// address = "0.0.0.0"
+///
+/// [upstream]
+/// nodes = ["https://grpc.penumbra.example:443", "http://127.0.0.1:8080"]
+///
+/// [cache]
+/// validators_ttl = 10
+///
+/// [routes]
+/// signing_infos = false
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct Config {
+    pub upstream: UpstreamConfig,
+    pub chain: ChainConfig,
+    pub cache: CacheConfig,
+    pub routes: RoutesConfig,
+    pub rate_limit: RateLimitConfig,
+    pub cors: CorsConfig,
+    pub log: LogConfig,
+    /// How often to check the TLS certificate files for changes, in seconds (0 disables).
+    pub tls_reload_interval: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            upstream: UpstreamConfig::default(),
+            chain: ChainConfig::default(),
+            cache: CacheConfig::default(),
+            routes: RoutesConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            cors: CorsConfig::default(),
+            log: LogConfig::default(),
+            tls_reload_interval: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct UpstreamConfig {
+    pub nodes: Vec<String>,
+    pub connect_timeout: u64,
+    pub keepalive_interval: u64,
+    pub keepalive_timeout: u64,
+    pub health_check_interval: u64,
+    pub max_height_lag: u64,
+    /// Age of the latest block, in seconds, past which the node is considered stalled.
+    pub max_block_age: u64,
+    /// Maximum number of upstream calls in flight at once; 0 is unlimited.
+    pub max_in_flight: usize,
+    /// Deadline of a single call, in seconds.
+    pub call_timeout: u64,
+    pub retries: u32,
+    /// Backoff before the first retry, in milliseconds; doubled for each further one.
+    pub retry_backoff: u64,
+    /// How many validator uptimes are fetched at once for a page of signing infos.
+    pub uptime_concurrency: usize,
+    /// Consecutive failures that open a node's circuit breaker; 0 disables it.
+    pub breaker_threshold: u32,
+    /// How long an open breaker keeps calls away from its node, in seconds.
+    pub breaker_cooldown: u64,
+    pub ca_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    pub tls_domain: Option<String>,
+    pub insecure_skip_verify: bool,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            connect_timeout: 5,
+            keepalive_interval: 30,
+            keepalive_timeout: 10,
+            health_check_interval: 5,
+            max_height_lag: 5,
+            max_block_age: 60,
+            max_in_flight: 0,
+            call_timeout: 10,
+            retries: 2,
+            retry_backoff: 100,
+            uptime_concurrency: 16,
+            breaker_threshold: 5,
+            breaker_cooldown: 30,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            tls_domain: None,
+            insecure_skip_verify: false,
+        }
+    }
+}
+
+/// Chain metadata the Cosmos responses need but the node does not report.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ChainConfig {
+    /// Reported when the node has no metadata for the staking token.
+    pub bond_denom: String,
+    pub valcons_prefix: String,
+    pub commission: CommissionConfig,
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            bond_denom: String::from("upenumbra"),
+            valcons_prefix: String::from("penumbravalcons"),
+            commission: CommissionConfig::default(),
+        }
+    }
+}
+
+/// Commission fields Penumbra has no equivalent for, reported for every validator.
+/// The rate itself is the sum of the validator's funding streams.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CommissionConfig {
+    pub max_rate: String,
+    pub max_change_rate: String,
+    pub update_time: String,
+}
+
+impl Default for CommissionConfig {
+    fn default() -> Self {
+        Self {
+            max_rate: String::from("1.0"),
+            max_change_rate: String::from("1.0"),
+            update_time: String::from("1970-01-01T00:00:00Z"),
+        }
+    }
+}
+
+/// Cache TTLs, in seconds; 0 disables caching of that query.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CacheConfig {
+    pub validators_ttl: u64,
+    pub params_ttl: u64,
+    pub proposals_ttl: u64,
+    pub uptime_ttl: u64,
+    /// Latest block and average block time, used to date unbonding validators.
+    pub clock_ttl: u64,
+    /// How long an expired entry may still be served while it is being refreshed.
+    pub stale_ttl: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            validators_ttl: 5,
+            params_ttl: 60,
+            proposals_ttl: 10,
+            uptime_ttl: 5,
+            clock_ttl: 5,
+            stale_ttl: 30,
+        }
+    }
+}
+
+/// Which routes are mounted, all of them by default.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RoutesConfig {
+    pub validators: bool,
+    pub validator_by_addr: bool,
+    pub pool: bool,
+    pub staking_params: bool,
+    pub slashing_params: bool,
+    pub signing_info: bool,
+    pub signing_infos: bool,
+    pub proposals: bool,
+    pub proposal: bool,
+    pub proposal_vote: bool,
+    pub proposal_tally: bool,
+    pub metrics: bool,
+    pub health: bool,
+    pub ready: bool,
+}
+
+impl Default for RoutesConfig {
+    fn default() -> Self {
+        Self {
+            validators: true,
+            validator_by_addr: true,
+            pool: true,
+            staking_params: true,
+            slashing_params: true,
+            signing_info: true,
+            signing_infos: true,
+            proposals: true,
+            proposal: true,
+            proposal_vote: true,
+            proposal_tally: true,
+            metrics: true,
+            health: true,
+            ready: true,
+        }
+    }
+}
+
+/// Per client IP token bucket, disabled unless `requests_per_second` is set.
+///
+/// ```toml
+/// [rate_limit]
+/// requests_per_second = 5
+/// burst = 20
+/// trusted_proxies = ["10.0.0.0/8"]
+///
+/// [rate_limit.costs]
+/// signing_infos = 20
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RateLimitConfig {
+    pub requests_per_second: f64,
+    pub burst: f64,
+    /// Addresses or networks of the reverse proxies allowed to set `X-Forwarded-For`.
+    pub trusted_proxies: Vec<String>,
+    /// Tokens a request to each route costs, by route name; unlisted routes cost 1 and
+    /// routes costing 0 are never limited.
+    pub costs: HashMap<String, f64>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            burst: 20.0,
+            trusted_proxies: vec![],
+            costs: HashMap::from([
+                // Fans out into one uptime query per validator on the page.
+                (String::from("signing_infos"), 10.0),
+                (String::from("proposals"), 2.0),
+                (String::from("proposal"), 2.0),
+                (String::from("health"), 0.0),
+                (String::from("ready"), 0.0),
+                (String::from("metrics"), 0.0),
+                (String::from("preflight"), 0.0),
+            ]),
+        }
+    }
+}
+
+/// Cross-origin access for browser clients such as explorers and wallets, open to
+/// any origin by default.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CorsConfig {
+    pub enabled: bool,
+    /// Allowed `Origin`s, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in preflight requests, or `*` for any.
+    pub allowed_headers: Vec<String>,
+    /// Response headers readable by scripts.
+    pub expose_headers: Vec<String>,
+    /// How long browsers may cache a preflight response, in seconds.
+    pub max_age: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowed_origins: vec![String::from("*")],
+            allowed_methods: vec![String::from("GET"), String::from("OPTIONS")],
+            allowed_headers: vec![String::from("*")],
+            expose_headers: vec![
+                String::from("X-Request-Id"),
+                String::from("X-Cache"),
+                String::from("X-Upstream-Node"),
+                String::from("Retry-After"),
+            ],
+            max_age: 86400,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One human readable line per event
+    #[default]
+    Human,
+    /// One JSON object per event, for log collectors
+    Json,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// `tracing` filter directives, in the `RUST_LOG` syntax.
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Human,
+            level: String::from("info"),
+        }
+    }
+}
+
+fn set<T: Serialize>(figment: Figment, key: &str, value: Option<T>) -> Figment {
+    match value {
+        Some(value) => figment.merge((key, value)),
+        None => figment,
+    }
+}
+
+/// Builds the figment shared by Rocket and the LCD, from lowest to highest precedence:
+/// built-in defaults, Rocket's own sources (`Rocket.toml`, `ROCKET_*`), the configuration
+/// file, `LCD_*` environment variables (`LCD_UPSTREAM__NODES`, `LCD_CACHE__PARAMS_TTL`, ...)
+/// and finally command line flags.
+pub fn figment(args: &Args) -> Result<Figment, String> {
+    let file = match &args.config {
+        Some(path) if !Path::new(path).is_file() => {
+            return Err(format!("configuration file {} not found", path));
+        }
+        Some(path) => Toml::file_exact(path),
+        None => Toml::file(DEFAULT_CONFIG_FILE),
+    };
+
+    let figment = rocket::Config::figment()
+        .join(Serialized::defaults(Config::default()))
+        .merge(file)
+        .merge(Env::prefixed("LCD_").split("__"));
+
+    let figment = set(figment, "upstream.nodes", (!args.node.is_empty()).then_some(&args.node));
+    let figment = set(figment, "port", args.port);
+    let figment = set(figment, "address", args.bind);
+    let figment = set(figment, "upstream.connect_timeout", args.connect_timeout);
+    let figment = set(figment, "upstream.keepalive_interval", args.keepalive_interval);
+    let figment = set(figment, "upstream.keepalive_timeout", args.keepalive_timeout);
+    let figment = set(figment, "upstream.ca_cert", args.node_ca_cert.as_ref());
+    let figment = set(figment, "upstream.client_cert", args.node_client_cert.as_ref());
+    let figment = set(figment, "upstream.client_key", args.node_client_key.as_ref());
+    let figment = set(figment, "upstream.tls_domain", args.node_tls_domain.as_ref());
+    let figment = set(figment, "upstream.insecure_skip_verify", args.node_insecure_skip_verify.then_some(true));
+    let figment = set(figment, "upstream.call_timeout", args.call_timeout);
+    let figment = set(figment, "upstream.retries", args.retries);
+    let figment = set(figment, "upstream.breaker_threshold", args.breaker_threshold);
+    let figment = set(figment, "upstream.breaker_cooldown", args.breaker_cooldown);
+    let figment = set(figment, "upstream.health_check_interval", args.health_check_interval);
+    let figment = set(figment, "upstream.max_height_lag", args.max_height_lag);
+    let figment = set(figment, "upstream.max_block_age", args.max_block_age);
+    let figment = set(figment, "tls.certs", args.tls_cert.as_ref());
+    let figment = set(figment, "tls.key", args.tls_key.as_ref());
+    let figment = set(figment, "tls.mutual.ca_certs", args.tls_client_ca.as_ref());
+    let figment = set(figment, "tls.mutual.mandatory", args.tls_client_ca.as_ref().map(|_| true));
+    let figment = set(figment, "tls_reload_interval", args.tls_reload_interval);
+    let figment = set(figment, "cache.validators_ttl", args.validators_ttl);
+    let figment = set(figment, "cache.params_ttl", args.params_ttl);
+    let figment = set(figment, "cache.proposals_ttl", args.proposals_ttl);
+    let figment = set(figment, "cache.uptime_ttl", args.uptime_ttl);
+    let figment = set(figment, "upstream.uptime_concurrency", args.uptime_concurrency);
+    let figment = set(figment, "cache.stale_ttl", args.cache_stale_ttl);
+    let figment = set(figment, "rate_limit.requests_per_second", args.rate_limit);
+    let figment = set(figment, "rate_limit.burst", args.rate_limit_burst);
+    let figment = set(figment, "rate_limit.trusted_proxies", (!args.trusted_proxy.is_empty()).then_some(&args.trusted_proxy));
+    let figment = set(figment, "upstream.max_in_flight", args.max_upstream_calls);
+    let figment = set(figment, "cors.allowed_origins", (!args.cors_origin.is_empty()).then_some(&args.cors_origin));
+    let figment = set(figment, "log.format", args.log_format);
+    let figment = set(figment, "log.level", args.log_level.as_ref());
+
+    Ok(figment)
+}
+
+impl Config {
+    /// Extracts the LCD settings from `figment` and checks them for consistency.
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        let config: Config = figment.extract().map_err(|error| error.to_string())?;
+
+        if config.upstream.nodes.is_empty() {
+            return Err("no upstream node configured, set --node or upstream.nodes".to_string());
+        }
+
+        if config.upstream.client_cert.is_some() != config.upstream.client_key.is_some() {
+            return Err("upstream.client_cert and upstream.client_key must be set together".to_string());
+        }
+
+        if config.upstream.uptime_concurrency == 0 {
+            return Err("upstream.uptime_concurrency must be greater than 0".to_string());
+        }
+
+        if config.upstream.call_timeout == 0 {
+            return Err("upstream.call_timeout must be greater than 0".to_string());
+        }
+
+        if config.upstream.health_check_interval == 0 {
+            return Err("upstream.health_check_interval must be greater than 0".to_string());
+        }
+
+        let rate_limit = &config.rate_limit;
+        if !rate_limit.requests_per_second.is_finite() || rate_limit.requests_per_second < 0.0 {
+            return Err("rate_limit.requests_per_second must be a finite number, at least 0".to_string());
+        }
+
+        if !rate_limit.burst.is_finite() {
+            return Err("rate_limit.burst must be a finite number".to_string());
+        }
+
+        if config.chain.bond_denom.is_empty() || config.chain.valcons_prefix.is_empty() {
+            return Err("chain.bond_denom and chain.valcons_prefix must not be empty".to_string());
+        }
+
+        for (name, value) in [
+            ("max_rate", &config.chain.commission.max_rate),
+            ("max_change_rate", &config.chain.commission.max_change_rate),
+        ] {
+            if value.parse::<f64>().is_err() {
+                return Err(format!("chain.commission.{} is not a decimal: {}", name, value));
+            }
+        }
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use rocket::fairing::AdHoc;
+use rocket::http::{Header, Method, Status};
+use rocket::request::Request;
+use rocket::response::Response;
+
+use crate::config::CorsConfig;
+
+fn allowed_origin<'a>(config: &CorsConfig, origin: &'a str) -> Option<&'a str> {
+    if config.allowed_origins.iter().any(|allowed| allowed == "*") {
+        return Some("*");
+    }
+
+    config
+        .allowed_origins
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        .then_some(origin)
+}
+
+fn is_preflight(request: &Request<'_>) -> bool {
+    request.method() == Method::Options
+        && request.headers().contains("Access-Control-Request-Method")
+}
+
+fn set_headers(config: &CorsConfig, request: &Request<'_>, response: &mut Response<'_>) {
+    let Some(origin) = request.headers().get_one("Origin") else {
+        return;
+    };
+    let Some(allowed) = allowed_origin(config, origin) else {
+        return;
+    };
+
+    response.set_header(Header::new("Access-Control-Allow-Origin", allowed.to_string()));
+    if allowed != "*" {
+        response.adjoin_header(Header::new("Vary", "Origin"));
+    }
+
+    if !is_preflight(request) {
+        if !config.expose_headers.is_empty() {
+            response.set_header(Header::new("Access-Control-Expose-Headers", config.expose_headers.join(", ")));
+        }
+        return;
+    }
+
+    // A wildcard in the allowed headers mirrors whatever the browser asks for.
+    let headers = match config.allowed_headers.iter().any(|header| header == "*") {
+        true => request
+            .headers()
+            .get_one("Access-Control-Request-Headers")
+            .unwrap_or_default()
+            .to_string(),
+        false => config.allowed_headers.join(", "),
+    };
+
+    response.set_header(Header::new("Access-Control-Allow-Methods", config.allowed_methods.join(", ")));
+    if !headers.is_empty() {
+        response.set_header(Header::new("Access-Control-Allow-Headers", headers));
+    }
+    response.set_header(Header::new("Access-Control-Max-Age", config.max_age.to_string()));
+}
+
+/// Answers CORS preflight requests for any path; the headers are added by [`fairing`].
+#[options("/<_..>")]
+pub fn preflight() -> Status {
+    Status::NoContent
+}
+
+/// Adds the CORS headers to responses to requests coming from an allowed origin.
+pub fn fairing(config: &CorsConfig) -> AdHoc {
+    let config = Arc::new(config.clone());
+
+    AdHoc::on_response("CORS", move |request, response| {
+        let config = config.clone();
+        Box::pin(async move {
+            set_headers(&config, request, response);
+        })
+    })
+}
@@ -0,0 +1,71 @@
+/// Fractional digits of the Cosmos SDK `Dec` type.
+const PRECISION: u32 = 18;
+
+/// Formats `numerator / denominator` as a Cosmos SDK decimal string with 18 fractional
+/// digits (`"0.050000000000000000"`), truncating like `sdk.Dec` does.
+pub fn from_ratio(numerator: u128, denominator: u128) -> String {
+    if denominator == 0 {
+        return format!("0.{}", "0".repeat(PRECISION as usize));
+    }
+
+    let scale = 10u128.pow(PRECISION);
+    let integer = numerator / denominator;
+    let remainder = numerator % denominator;
+
+    // remainder < denominator, so this only overflows for denominators above ~3.4e20.
+    let fraction = match remainder.checked_mul(scale) {
+        Some(scaled) => scaled / denominator,
+        None => ((remainder as f64 / denominator as f64) * scale as f64) as u128,
+    };
+
+    format!("{}.{:0width$}", integer, fraction, width = PRECISION as usize)
+}
+
+/// Basis points (1/10_000) as a Cosmos decimal.
+pub fn from_bps(bps: u64) -> String {
+    from_ratio(bps as u128, 10_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_with_eighteen_digits() {
+        assert_eq!(from_ratio(3, 2), "1.500000000000000000");
+        assert_eq!(from_ratio(0, 7), "0.000000000000000000");
+        assert_eq!(from_ratio(42, 1), "42.000000000000000000");
+    }
+
+    #[test]
+    fn truncates_instead_of_rounding() {
+        assert_eq!(from_ratio(1, 3), "0.333333333333333333");
+        assert_eq!(from_ratio(2, 3), "0.666666666666666666");
+    }
+
+    #[test]
+    fn zero_denominator_is_zero() {
+        assert_eq!(from_ratio(10, 0), "0.000000000000000000");
+    }
+
+    #[test]
+    fn large_denominators_fall_back_to_floats() {
+        // remainder * 10^18 overflows u128 here.
+        let denominator = u128::MAX / 2;
+        assert_eq!(from_ratio(denominator / 4, denominator), "0.250000000000000000");
+    }
+
+    #[test]
+    fn large_numerators_keep_the_integer_part_exact() {
+        let numerator = u128::MAX;
+
+        assert_eq!(from_ratio(numerator, 1), format!("{}.000000000000000000", u128::MAX));
+    }
+
+    #[test]
+    fn basis_points() {
+        assert_eq!(from_bps(500), "0.050000000000000000");
+        assert_eq!(from_bps(10_000), "1.000000000000000000");
+        assert_eq!(from_bps(1), "0.000100000000000000");
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Display;
+
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::{json, Json, Value};
+use tonic::Code;
+
+/// Errors returned by the route handlers, rendered as gRPC-gateway error bodies
+/// (`{"code": ..., "message": ..., "details": []}`) like the Cosmos SDK LCD does.
+#[derive(Debug)]
+pub enum Error {
+    /// The upstream node could not be reached or rejected the call.
+    Upstream(Box<tonic::Status>),
+    /// The upstream node returned something we could not decode.
+    Decode(String),
+    /// A path or query parameter is malformed or refers to nothing.
+    InvalidArgument(String),
+    /// The requested object does not exist.
+    NotFound(String),
+    /// The client went over its rate limit.
+    ResourceExhausted(String),
+}
+
+impl Error {
+    pub fn decode(error: impl Display) -> Self {
+        Error::Decode(error.to_string())
+    }
+
+    pub fn missing(field: &str) -> Self {
+        Error::Decode(format!("upstream response is missing {}", field))
+    }
+
+    pub fn invalid_param(name: &str, value: &str) -> Self {
+        Error::InvalidArgument(format!("invalid {}: {}", name, value))
+    }
+
+    fn code(&self) -> Code {
+        match self {
+            Error::Upstream(status) => status.code(),
+            Error::Decode(_) => Code::Internal,
+            Error::InvalidArgument(_) => Code::InvalidArgument,
+            Error::NotFound(_) => Code::NotFound,
+            Error::ResourceExhausted(_) => Code::ResourceExhausted,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::Upstream(status) => status.message().to_string(),
+            Error::Decode(message) => message.clone(),
+            Error::InvalidArgument(message) => message.clone(),
+            Error::NotFound(message) => message.clone(),
+            Error::ResourceExhausted(message) => message.clone(),
+        }
+    }
+}
+
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Error::Upstream(Box::new(status))
+    }
+}
+
+/// Maps a gRPC status code to the HTTP status grpc-gateway would answer with.
+fn http_status(code: Code) -> Status {
+    match code {
+        Code::Ok => Status::Ok,
+        Code::Cancelled => Status::new(499),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => Status::BadRequest,
+        Code::DeadlineExceeded => Status::GatewayTimeout,
+        Code::NotFound => Status::NotFound,
+        Code::AlreadyExists | Code::Aborted => Status::Conflict,
+        Code::PermissionDenied => Status::Forbidden,
+        Code::Unauthenticated => Status::Unauthorized,
+        Code::ResourceExhausted => Status::TooManyRequests,
+        Code::Unimplemented => Status::NotImplemented,
+        Code::Unavailable => Status::ServiceUnavailable,
+        Code::Unknown | Code::Internal | Code::DataLoss => Status::InternalServerError,
+    }
+}
+
+/// Maps an HTTP status produced by Rocket itself back to a gRPC status code.
+fn grpc_code(status: Status) -> Code {
+    match status.code {
+        400 | 422 => Code::InvalidArgument,
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::NotFound,
+        409 => Code::Aborted,
+        429 => Code::ResourceExhausted,
+        499 => Code::Cancelled,
+        501 => Code::Unimplemented,
+        503 => Code::Unavailable,
+        504 => Code::DeadlineExceeded,
+        _ => Code::Unknown,
+    }
+}
+
+fn error_body(code: Code, message: &str) -> Value {
+    json!({
+        "code": code as i32,
+        "message": message,
+        "details": []
+    })
+}
+
+impl<'r> Responder<'r, 'static> for Error {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let code = self.code();
+        let body = error_body(code, &self.message());
+
+        status::Custom(http_status(code), Json(body)).respond_to(request)
+    }
+}
+
+/// Renders errors raised by Rocket itself (unknown routes, guard failures) in the same shape.
+#[catch(default)]
+pub fn default_catcher(status: Status, _request: &Request) -> status::Custom<Json<Value>> {
+    let message = status.reason().unwrap_or("Unknown error");
+
+    let code = grpc_code(status);
+
+    // Answer with the status matching the code, e.g. 400 rather than Rocket's 422 for a
+    // malformed query parameter reported as InvalidArgument.
+    status::Custom(http_status(code), Json(error_body(code, message)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::local::blocking::Client;
+
+    use super::*;
+
+    #[get("/?<number>")]
+    fn number(number: u64) -> String {
+        number.to_string()
+    }
+
+    #[test]
+    fn catcher_answers_with_the_status_of_the_code() {
+        let rocket = rocket::build()
+            .mount("/", routes![number])
+            .register("/", catchers![default_catcher]);
+        let client = Client::untracked(rocket).unwrap();
+
+        let response = client.get("/?number=abc").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(response.into_json::<Value>().unwrap()["code"], Code::InvalidArgument as i32);
+
+        let response = client.get("/unknown").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Instant;
+
+use rocket::fairing::AdHoc;
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome, Request};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::config::{LogConfig, LogFormat};
+
+/// Longest client-provided `X-Request-Id` we pass through; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Installs the global `tracing` subscriber. Rocket's own `log` records are forwarded to it,
+/// so its launch and request output ends up in the same format.
+pub fn init(config: &LogConfig) -> Result<(), String> {
+    let filter = EnvFilter::try_new(&config.level)
+        .map_err(|error| format!("invalid log.level {}: {}", config.level, error))?;
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match config.format {
+        LogFormat::Human => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).try_init(),
+    }
+    .map_err(|error| error.to_string())
+}
+
+/// Identifier of the request being served, taken from the client's `X-Request-Id` header
+/// when it sends a sane one and generated otherwise. It is attached to every log line
+/// and upstream call span of the request and returned in the `X-Request-Id` header.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn request_id(request: &Request<'_>) -> RequestId {
+    request
+        .local_cache(|| {
+            let id = request
+                .headers()
+                .get_one("X-Request-Id")
+                .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+                .filter(|id| id.chars().all(|c| c.is_ascii_graphic()))
+                .map(str::to_string)
+                .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+            RequestId(id)
+        })
+        .clone()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(request_id(request))
+    }
+}
+
+/// Assigns request IDs and logs every response with its status and duration.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_ignite("Request logging", |rocket| async {
+        rocket
+            .attach(AdHoc::on_request("Request ID", |request, _| Box::pin(async move {
+                request_id(request);
+                request.local_cache(Instant::now);
+            })))
+            .attach(AdHoc::on_response("Request log", |request, response| Box::pin(async move {
+                let id = request_id(request);
+                let started = request.local_cache(Instant::now);
+                let status = response.status().code;
+
+                let route = request.route().and_then(|route| route.name.as_deref());
+                let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+                if status >= 500 {
+                    tracing::warn!(request_id = %id, method = %request.method(), uri = %request.uri(), route, status, elapsed_ms, "request failed");
+                } else {
+                    tracing::info!(request_id = %id, method = %request.method(), uri = %request.uri(), route, status, elapsed_ms, "request served");
+                }
+
+                response.set_header(Header::new("X-Request-Id", id.0));
+            })))
+    })
+}
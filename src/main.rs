@@ -1,28 +1,35 @@
 #[macro_use]
 extern crate rocket;
 
-use penumbra_proto::util::tendermint_proxy::v1::SyncInfo;
+use penumbra_proto::Message;
+use rocket::serde::json::{json, Json, Value};
+use rocket::figment::Figment;
+use rocket::http::Status;
 use rocket::response::status;
-use rocket::serde::json::{Json, json, Value};
-use rocket::State;
+use rocket::{Build, Rocket, State};
 use clap::Parser;
-use futures::TryStreamExt;
-use std::net::IpAddr;
-use chrono::DateTime;
+use futures::{stream, StreamExt, TryStreamExt};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use chrono::{DateTime, SecondsFormat, Utc};
 
 use penumbra_proto::{
     core::app::v1::{
-        query_service_client::QueryServiceClient as AppQueryServiceClient,
         AppParametersRequest,
         AppParameters,
     },
+    core::component::sct::v1::EpochByHeightRequest,
+    core::component::shielded_pool::v1::AssetMetadataByIdRequest,
     core::component::stake::v1::{
-        query_service_client::QueryServiceClient as StakeQueryServiceClient,
+        GetValidatorInfoRequest,
         ValidatorInfoRequest,
-        ValidatorUptimeRequest,        
+        ValidatorUptimeRequest,
+        StakeParameters,
+        Uptime as ProtoUptime,
     },
     core::component::governance::v1::{
-        query_service_client::QueryServiceClient as GovernanceQueryServiceClient,
         ProposalDataRequest,
         ProposalDataResponse,
         ProposalListRequest,
@@ -34,144 +41,517 @@ use penumbra_proto::{
         proposal_state::State as ProposalState,
         proposal_outcome::Outcome,
         proposal_state::Finished,
+        proposal::Payload as ProposalPayload,
         Proposal,
     },
+    core::transaction::v1::TransactionPlan,
     penumbra::core::keys::v1::IdentityKey as ProtoIdentityKey,
     util::tendermint_proxy::v1::{
-        tendermint_proxy_service_client::TendermintProxyServiceClient,
-        GetStatusRequest,
-        GetStatusResponse,
         GetBlockByHeightRequest,
         GetBlockByHeightResponse,
     }
 };
+use penumbra_asset::STAKING_TOKEN_ASSET_ID;
 use penumbra_stake::{
-    IdentityKey, Uptime,
+    FundingStream, IdentityKey, Uptime,
     validator::{self, BondingState, State as ValidatorState},
 };
 
-use tonic::transport::{Channel, ClientTlsConfig};
+mod breaker;
+mod cache;
+mod clock;
+mod config;
+mod cors;
+mod decimal;
+mod error;
+mod logging;
+mod metrics;
+mod pagination;
+mod ratelimit;
+mod tls;
+mod transport;
+mod upstream;
+
+use cache::{Cache, CacheStatus, Cached};
+use clock::{get_chain_clock, get_sync_info, ChainClock};
+use config::{Args, Config};
+use error::Error;
+use logging::RequestId;
+use pagination::PageRequest;
+use ratelimit::RateLimiter;
+use upstream::Upstream;
+
+async fn get_validators(
+    upstream: &Upstream,
+    cache: &Cache,
+) -> Result<(Arc<Vec<validator::Info>>, CacheStatus), Error> {
+    let upstream = upstream.clone();
+
+    cache.validator_info.get(|| async move {
+        upstream.call(|upstream| async move {
+            upstream
+                .stake()
+                .validator_info(ValidatorInfoRequest {
+                    show_inactive: true,
+                    ..Default::default()
+                })
+                .await?
+                .into_inner()
+                .try_collect::<Vec<_>>()
+                .await
+                .map_err(Error::from)
+        })
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<validator::Info>, _>>()
+        .map_err(Error::decode)
+    }).await
+}
 
-#[derive(Parser)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(short, long)]
-    node: String,
+async fn get_validator(upstream: &Upstream, identity_key: IdentityKey) -> Result<validator::Info, Error> {
+    let not_found = || Error::NotFound(format!("validator {} not found", identity_key));
+
+    let response = upstream
+        .call(|upstream| async move {
+            let identity_key: ProtoIdentityKey = identity_key.into();
+            Ok(upstream
+                .stake()
+                .get_validator_info(GetValidatorInfoRequest {
+                    identity_key: Some(identity_key),
+                })
+                .await?)
+        })
+        .await
+        .map_err(|error| match error {
+            Error::Upstream(status) if status.code() == tonic::Code::NotFound => not_found(),
+            error => error,
+        })?;
+
+    response
+        .into_inner()
+        .validator_info
+        .ok_or_else(not_found)?
+        .try_into()
+        .map_err(Error::decode)
+}
 
-    #[arg(short, long, default_value_t = 8000)]
-    port: i32,
+async fn get_app_parameters(
+    upstream: &Upstream,
+    cache: &Cache,
+) -> Result<(Arc<AppParameters>, CacheStatus), Error> {
+    let upstream = upstream.clone();
 
-    #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
-    bind: String,
+    cache.app_parameters.get(|| async move {
+        upstream
+            .call(|upstream| async move {
+                Ok(upstream.app().app_parameters(tonic::Request::new(AppParametersRequest {})).await?)
+            })
+            .await?
+            .into_inner()
+            .app_parameters
+            .ok_or_else(|| Error::missing("app_parameters"))?
+            .try_into()
+            .map_err(Error::decode)
+    }).await
 }
 
-#[get("/cosmos/staking/v1beta1/validators?<status>")]
-async fn validators(status: Option<String>, args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-       .unwrap()
-        .connect()
-        .await
-        .unwrap();
+/// Base denom of the staking token, from its asset metadata. Falls back to the configured
+/// `chain.bond_denom` when the node has no metadata for it or cannot be asked, in which
+/// case the next request asks again.
+async fn get_bond_denom(
+    upstream: &Upstream,
+    cache: &Cache,
+    config: &Config,
+) -> (Arc<String>, CacheStatus) {
+    let fallback = config.chain.bond_denom.clone();
+    let upstream = upstream.clone();
+    let missing = fallback.clone();
+
+    let result = cache.bond_denom.get(|| async move {
+        let metadata = upstream
+            .call(|upstream| async move {
+                Ok(upstream
+                    .shielded_pool()
+                    .asset_metadata_by_id(AssetMetadataByIdRequest {
+                        asset_id: Some((*STAKING_TOKEN_ASSET_ID).into()),
+                    })
+                    .await?)
+            })
+            .await?
+            .into_inner()
+            .denom_metadata;
+
+        Ok(metadata.map(|metadata| metadata.base).unwrap_or(missing))
+    }).await;
 
-    let mut client = StakeQueryServiceClient::new(channel);
+    match result {
+        Ok(result) => result,
+        Err(error) => {
+            tracing::warn!(error = %error.message(), "could not fetch the staking token metadata, using the configured bond denom");
+            (Arc::new(fallback), CacheStatus::Miss)
+        }
+    }
+}
 
-    let validators: Vec<validator::Info> = client
-        .validator_info(ValidatorInfoRequest {
-            show_inactive: true,
-            ..Default::default()
+async fn get_proposals(
+    upstream: &Upstream,
+    cache: &Cache,
+) -> Result<(Arc<Vec<ProposalListResponse>>, CacheStatus), Error> {
+    let upstream = upstream.clone();
+
+    cache.proposal_list.get(|| async move {
+        upstream.call(|upstream| async move {
+            upstream
+                .governance()
+                .proposal_list(ProposalListRequest { inactive: true })
+                .await?
+                .into_inner()
+                .try_collect::<Vec<_>>()
+                .await
+                .map_err(Error::from)
+        }).await
+    }).await
+}
+
+/// Index of the current epoch, which the validator rates are for.
+async fn get_current_epoch(upstream: &Upstream, cache: &Cache) -> Result<(Arc<u64>, CacheStatus), Error> {
+    let upstream = upstream.clone();
+
+    cache.current_epoch.get(|| async move {
+        let height = get_sync_info(&upstream).await?.latest_block_height;
+        let epoch = upstream
+            .call(|upstream| async move {
+                Ok(upstream
+                    .sct()
+                    .epoch_by_height(EpochByHeightRequest { height })
+                    .await?)
+            })
+            .await?
+            .into_inner()
+            .epoch
+            .ok_or_else(|| Error::missing("epoch"))?;
+
+        Ok(epoch.index)
+    }).await
+}
+
+/// Validator reward and exchange rates are fixed-point numbers scaled by 10^8.
+const EXCHANGE_RATE_SCALE: u128 = 100_000_000;
+
+/// Slashing penalties are in basis points of basis points.
+const SLASHING_PENALTY_SCALE: u128 = 100_000_000;
+
+fn bond_status(bonding_state: &BondingState) -> &'static str {
+    match bonding_state {
+        BondingState::Bonded => "BOND_STATUS_BONDED",
+        BondingState::Unbonding { unbonds_at_height: _ } => "BOND_STATUS_UNBONDING",
+        BondingState::Unbonded => "BOND_STATUS_UNBONDED",
+    }
+}
+
+/// Maps a validator to the Cosmos `Validator` object, shared by the list and single validator routes.
+fn map_validator(validator: &validator::Info, unbonding_time: Option<DateTime<Utc>>, config: &Config) -> Value {
+    let unbonding_height = match validator.status.bonding_state {
+        BondingState::Unbonding { unbonds_at_height } => unbonds_at_height,
+        _ => 0,
+    };
+
+    // pd does not expose the supply of a validator's delegation tokens, only its voting
+    // power, which is that supply already valued in staking tokens at the current exchange
+    // rate. So `tokens` is the voting power as is, and `delegator_shares` the delegation
+    // tokens it stands for, which is only as exact as the exchange rate.
+    let tokens = validator.status.voting_power.value();
+    let exchange_rate = validator.rate_data.validator_exchange_rate.value();
+    let delegator_shares = decimal::from_ratio(tokens * EXCHANGE_RATE_SCALE, exchange_rate);
+
+    let commission = &config.chain.commission;
+    let commission_bps: u64 = validator
+        .validator
+        .funding_streams
+        .iter()
+        .map(|stream| stream.rate_bps() as u64)
+        .sum();
+
+    json!({
+        "operator_address": validator.validator.identity_key.to_string(),
+        "consensus_pubkey": {
+            "@type": "/cosmos.crypto.ed25519.PubKey",
+            "key": base64::encode(validator.validator.consensus_key.to_bytes()),
+        },
+        "jailed": validator.status.state == ValidatorState::Jailed,
+        "status": bond_status(&validator.status.bonding_state),
+        "tokens": tokens.to_string(),
+        "delegator_shares": delegator_shares,
+        "description": {
+            "moniker": validator.validator.name,
+            "identity": "",
+            "website": validator.validator.website,
+            "security_contact": "",
+            "details": validator.validator.description,
+        },
+        "unbonding_height": unbonding_height.to_string(),
+        "unbonding_time": unbonding_time.unwrap_or_default().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "commission": {
+            "commission_rates": {
+                "rate": decimal::from_bps(commission_bps),
+                "max_rate": commission.max_rate,
+                "max_change_rate": commission.max_change_rate
+            },
+            "update_time": commission.update_time,
+        },
+        "min_self_delegation": "0"
+    })
+}
+
+fn map_funding_streams(validator: &validator::Info) -> Vec<Value> {
+    validator
+        .validator
+        .funding_streams
+        .iter()
+        .map(|stream| {
+            let recipient = match stream {
+                FundingStream::ToAddress { address, .. } => address.to_string(),
+                FundingStream::ToCommunityPool { .. } => String::from("community_pool"),
+            };
+
+            json!({
+                "recipient": recipient,
+                "rate_bps": stream.rate_bps(),
+                "rate": decimal::from_bps(stream.rate_bps() as u64),
+            })
         })
-        .await
-        .unwrap()
-        .into_inner()
-        .try_collect::<Vec<_>>()
-        .await
-        .unwrap()
-        .into_iter()
-        .map(TryInto::try_into)
-        .collect::<Result<Vec<validator::Info>, _>>()
-        .unwrap();
+        .collect()
+}
+
+/// The Penumbra validator fields that have no Cosmos counterpart, returned under `penumbra`
+/// when a validator route is called with `extended=true`. The funding streams break the
+/// Cosmos commission rate down by recipient, and the rates are for `epoch_index`.
+fn map_validator_extension(validator: &validator::Info, epoch_index: u64) -> Value {
+    let rate_data = &validator.rate_data;
+
+    json!({
+        "identity_key": validator.validator.identity_key.to_string(),
+        "governance_key": validator.validator.governance_key.to_string(),
+        "sequence_number": validator.validator.sequence_number,
+        "enabled": validator.validator.enabled,
+        "state": validator_state_name(&validator.status.state),
+        "voting_power": validator.status.voting_power.value().to_string(),
+        "funding_streams": map_funding_streams(validator),
+        "rate_data": {
+            "epoch_index": epoch_index.to_string(),
+            "validator_reward_rate": rate_data.validator_reward_rate.value().to_string(),
+            "validator_exchange_rate": rate_data.validator_exchange_rate.value().to_string(),
+        },
+    })
+}
+
+/// Maps a validator for the validator routes, adding the Penumbra fields when given the
+/// current epoch, which is only fetched for `extended=true`.
+fn map_validator_output(validator: &validator::Info, unbonding_time: Option<DateTime<Utc>>, epoch_index: Option<u64>, config: &Config) -> Value {
+    let mut value = map_validator(validator, unbonding_time, config);
+    if let Some(epoch_index) = epoch_index {
+        value["penumbra"] = map_validator_extension(validator, epoch_index);
+    }
+    value
+}
+
+/// When the validator finishes unbonding: the time of its unbonding block once that block
+/// exists, estimated from the average block time until then. `None` unless it is unbonding.
+async fn get_unbonding_time(
+    upstream: &Upstream,
+    cache: &Cache,
+    clock: &ChainClock,
+    validator: &validator::Info,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    let BondingState::Unbonding { unbonds_at_height } = validator.status.bonding_state else {
+        return Ok(None);
+    };
+
+    if unbonds_at_height > clock.latest_block_height {
+        return Ok(clock.estimate(unbonds_at_height));
+    }
+
+    let (time, _) = get_block_timestamp(upstream, cache, unbonds_at_height).await?;
+    Ok(DateTime::from_timestamp(*time, 0))
+}
+
+const BOND_STATUSES: [&str; 3] = ["BOND_STATUS_BONDED", "BOND_STATUS_UNBONDING", "BOND_STATUS_UNBONDED"];
+
+/// Splits repeated and comma separated query values.
+fn filter_values(values: &[String]) -> impl Iterator<Item = &str> {
+    values
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Parses the `status` filter; `None` when every bond status is wanted, either because
+/// none was given or because `BOND_STATUS_UNSPECIFIED` was.
+fn parse_bond_statuses(values: &[String]) -> Result<Option<Vec<&'static str>>, Error> {
+    let mut statuses = vec![];
+    let mut unspecified = false;
+
+    for value in filter_values(values) {
+        if value == "BOND_STATUS_UNSPECIFIED" {
+            unspecified = true;
+            continue;
+        }
+
+        let status = BOND_STATUSES
+            .into_iter()
+            .find(|status| *status == value)
+            .ok_or_else(|| Error::invalid_param("validator status", value))?;
+        statuses.push(status);
+    }
+
+    Ok((!unspecified && !statuses.is_empty()).then_some(statuses))
+}
+
+/// Every validator state, for the `validator_state` filter to accept their names.
+const VALIDATOR_STATES: [ValidatorState; 6] = [
+    ValidatorState::Defined,
+    ValidatorState::Inactive,
+    ValidatorState::Active,
+    ValidatorState::Jailed,
+    ValidatorState::Tombstoned,
+    ValidatorState::Disabled,
+];
+
+fn validator_state_name(state: &ValidatorState) -> &'static str {
+    match state {
+        ValidatorState::Defined => "defined",
+        ValidatorState::Inactive => "inactive",
+        ValidatorState::Active => "active",
+        ValidatorState::Jailed => "jailed",
+        ValidatorState::Tombstoned => "tombstoned",
+        ValidatorState::Disabled => "disabled",
+    }
+}
+
+/// Parses the Penumbra `validator_state` filter, since the three Cosmos bond statuses do
+/// not tell apart jailed, tombstoned or disabled validators.
+fn parse_validator_states(values: &[String]) -> Result<Option<Vec<&'static str>>, Error> {
+    let states = filter_values(values)
+        .map(|value| {
+            VALIDATOR_STATES
+                .iter()
+                .map(validator_state_name)
+                .find(|name| name.eq_ignore_ascii_case(value))
+                .ok_or_else(|| Error::invalid_param("validator state", value))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((!states.is_empty()).then_some(states))
+}
+
+/// Parses a boolean query parameter; as `Option<bool>`, Rocket would silently turn a
+/// malformed value into `None`. A bare `?extended` counts as true.
+fn parse_flag(name: &str, value: Option<&str>) -> Result<bool, Error> {
+    match value {
+        None => Ok(false),
+        Some(value) => match value.to_ascii_lowercase().as_str() {
+            "" | "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(Error::invalid_param(name, value)),
+        },
+    }
+}
+
+#[get("/cosmos/staking/v1beta1/validators?<status>&<validator_state>&<extended>&<pagination>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn validators(status: Vec<String>, validator_state: Vec<String>, extended: Option<&str>, pagination: PageRequest, upstream: &State<Upstream>, cache: &State<Cache>, config: &State<Config>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let statuses = parse_bond_statuses(&status)?;
+    let states = parse_validator_states(&validator_state)?;
+    let extended = parse_flag("extended", extended)?;
+
+    let (validators, cache_status) = get_validators(upstream, cache).await?;
 
     let mut result: Vec<_> = vec![];
-    for validator in validators {
-        let validator_status = match validator.status.bonding_state {
-            BondingState::Bonded => "BOND_STATUS_BONDED",
-            BondingState::Unbonding { unbonds_at_height: _ } => "BOND_STATUS_UNBONDING",
-            BondingState::Unbonded => "BOND_STATUS_UNBONDED",
-        };
+    for validator in validators.iter() {
+        let validator_status = bond_status(&validator.status.bonding_state);
+        let state = validator_state_name(&validator.status.state);
 
-        if !status.is_none() && status != Some(validator_status.to_owned()) {
+        if statuses.as_ref().is_some_and(|statuses| !statuses.contains(&validator_status)) {
             continue;
         }
 
-        result.push(json!({
-            "operator_address": validator.validator.identity_key.to_string(),
-            "consensus_pubkey": {
-                "@type": "/cosmos.crypto.ed25519.PubKey",
-                "key": base64::encode(validator.validator.consensus_key.to_bytes()),
-            },
-            "jailed": validator.status.state == ValidatorState::Jailed,
-            "status": validator_status,
-            "tokens": validator.status.voting_power.value().to_string(),
-            "delegator_shares": validator.status.voting_power.value().to_string(),
-            "description": {
-                "moniker": validator.validator.name,
-                "identity": "",
-                "website": validator.validator.website,
-                "security_contact": "",
-                "details": validator.validator.description,
-            },
-            "unbonding_height": "0", // TODO
-            "unbonding_time": "1970-01-01T00:00:00Z", // TODO
-            "commission": {
-                "commission_rates": {
-                    "rate": "0.05",
-                    "max_rate": "1.0",
-                    "max_change_rate": "1.0"
-                },
-                "update_time": "2023-08-04T06:00:00.000000000Z" // TODO
-            },
-            "min_self_delegation": "0"
-        }));
+        if states.as_ref().is_some_and(|states| !states.contains(&state)) {
+            continue;
+        }
+
+        let operator_address = validator.validator.identity_key.to_string();
+        result.push((operator_address.into_bytes(), validator));
     }
 
-    json!({
-        "validators": result,
-        "pagination": {
-            "next_key": null,
-            "total": result.len().to_string()
+    let page = pagination.paginate(result)?;
+
+    // Only worth the extra upstream calls when some validator of the page is unbonding.
+    let (clock, cache_status) = match page.items.iter().any(|validator| matches!(validator.status.bonding_state, BondingState::Unbonding { .. })) {
+        true => {
+            let (clock, clock_status) = get_chain_clock(upstream, cache).await?;
+            (Some(clock), cache_status.merge(clock_status))
         }
-    })
+        false => (None, cache_status),
+    };
+
+    let (epoch_index, cache_status) = match extended {
+        true => {
+            let (epoch_index, epoch_status) = get_current_epoch(upstream, cache).await?;
+            (Some(*epoch_index), cache_status.merge(epoch_status))
+        }
+        false => (None, cache_status),
+    };
+
+    let mut items = vec![];
+    for validator in page.items.iter() {
+        let unbonding_time = match &clock {
+            Some(clock) => get_unbonding_time(upstream, cache, clock, validator).await?,
+            None => None,
+        };
+
+        items.push(map_validator_output(validator, unbonding_time, epoch_index, config));
+    }
+
+    Ok(Cached(json!({
+        "validators": items,
+        "pagination": page.pagination(),
+    }), cache_status))
 }
 
-#[get("/cosmos/staking/v1beta1/pool")]
-async fn pool(args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-       .unwrap()
-        .connect()
-        .await
-        .unwrap();
+#[get("/cosmos/staking/v1beta1/validators/<validator_addr>?<extended>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn validator_by_addr(validator_addr: &str, extended: Option<&str>, upstream: &State<Upstream>, cache: &State<Cache>, config: &State<Config>, request_id: RequestId) -> Result<Value, Error> {
+    let identity_key = validator_addr
+        .parse::<IdentityKey>()
+        .map_err(|_| Error::invalid_param("validator address", validator_addr))?;
+    let extended = parse_flag("extended", extended)?;
 
-    let mut client = StakeQueryServiceClient::new(channel);
+    let validator_info = get_validator(upstream, identity_key).await?;
 
-    let validators: Vec<validator::Info> = client
-        .validator_info(ValidatorInfoRequest {
-            show_inactive: true,
-            ..Default::default()
-        })
-        .await
-        .unwrap()
-        .into_inner()
-        .try_collect::<Vec<_>>()
-        .await
-        .unwrap()
-        .into_iter()
-        .map(TryInto::try_into)
-        .collect::<Result<Vec<validator::Info>, _>>()
-        .unwrap();
+    let unbonding_time = match validator_info.status.bonding_state {
+        BondingState::Unbonding { .. } => {
+            let (clock, _) = get_chain_clock(upstream, cache).await?;
+            get_unbonding_time(upstream, cache, &clock, &validator_info).await?
+        }
+        _ => None,
+    };
+
+    let epoch_index = match extended {
+        true => Some(*get_current_epoch(upstream, cache).await?.0),
+        false => None,
+    };
+
+    Ok(json!({
+        "validator": map_validator_output(&validator_info, unbonding_time, epoch_index, config),
+    }))
+}
+
+#[get("/cosmos/staking/v1beta1/pool")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn pool(upstream: &State<Upstream>, cache: &State<Cache>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let (validators, cache_status) = get_validators(upstream, cache).await?;
 
     let bonded_tokens: u128 = validators.iter()
         .filter(|validator| validator.status.bonding_state == BondingState::Bonded)
@@ -183,249 +563,481 @@ async fn pool(args: &State<Args>) -> Value {
         .map(|validator| validator.status.voting_power.value())
         .sum();
 
-    json!({
+    Ok(Cached(json!({
         "pool": {
             "bonded_tokens": bonded_tokens.to_string(),
             "not_bonded_tokens": not_bonded_tokens.to_string(),
         }
-    })
+    }), cache_status))
 }
 
-#[get("/cosmos/slashing/v1beta1/params")]
-async fn slashing_params(args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
+/// The unbonding delay as a Cosmos duration (`"1209600s"`). The delay is a number of blocks,
+/// so its length in time depends on how fast blocks are being produced; it is `None` when
+/// the block time cannot be measured, for the caller to leave it out rather than fail.
+async fn unbonding_duration(stake_params: &StakeParameters, upstream: &Upstream, cache: &Cache) -> (Option<String>, CacheStatus) {
+    match get_chain_clock(upstream, cache).await {
+        Ok((clock, cache_status)) => {
+            let seconds = (stake_params.unbonding_delay as f64 * clock.block_time).round() as u64;
+            (Some(format!("{}s", seconds)), cache_status)
+        }
+        Err(error) => {
+            tracing::warn!(error = %error.message(), "could not measure the block time, leaving out the unbonding duration");
+            (None, CacheStatus::Miss)
+        }
+    }
+}
 
-    let mut client = AppQueryServiceClient::new(channel);
-    let params: AppParameters = client
-        .app_parameters(tonic::Request::new(AppParametersRequest {}))
-        .await
-        .unwrap()
-        .into_inner()
-        .app_parameters
-        .unwrap()
-        .try_into()
-        .unwrap();
+#[get("/cosmos/slashing/v1beta1/params")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn slashing_params(upstream: &State<Upstream>, cache: &State<Cache>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let (params, cache_status) = get_app_parameters(upstream, cache).await?;
+    let stake_params = params
+        .stake_params
+        .as_ref()
+        .ok_or_else(|| Error::missing("stake_params"))?;
+    let window = stake_params.signed_blocks_window_len;
+    let min_signed_per_window = decimal::from_ratio(
+        window.saturating_sub(stake_params.missed_blocks_maximum) as u128,
+        window as u128,
+    );
 
-    let stake_params = params.stake_params.unwrap();
-    let min_signed_per_window = 1.0 - (stake_params.missed_blocks_maximum as f64)
-        / (stake_params.signed_blocks_window_len as f64);
+    let mut result = json!({
+        "signed_blocks_window": window.to_string(),
+        "min_signed_per_window": min_signed_per_window,
+        "slash_fraction_double_sign": decimal::from_ratio(stake_params.slashing_penalty_misbehavior as u128, SLASHING_PENALTY_SCALE),
+        "slash_fraction_downtime": decimal::from_ratio(stake_params.slashing_penalty_downtime as u128, SLASHING_PENALTY_SCALE),
+    });
+
+    // Penumbra has no jail term; a jailed validator's delegations unbond over the
+    // unbonding delay, which is the closest equivalent.
+    let (jail_duration, clock_status) = unbonding_duration(stake_params, upstream, cache).await;
+    if let Some(jail_duration) = jail_duration {
+        result["downtime_jail_duration"] = json!(jail_duration);
+    }
+    let cache_status = cache_status.merge(clock_status);
 
-    json!({
-        "params": {
-            "signed_blocks_window": stake_params.signed_blocks_window_len.to_string(),
-            "min_signed_per_window": min_signed_per_window.to_string(),
-            "downtime_jail_duration": "0s",
-            "slash_fraction_double_sign": "0.0",
-            "slash_fraction_downtime": "0.0",
-        }
-    })
+    Ok(Cached(json!({
+        "params": result,
+    }), cache_status))
 }
 
 #[get("/cosmos/staking/v1beta1/params")]
-async fn staking_params(args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn staking_params(upstream: &State<Upstream>, cache: &State<Cache>, config: &State<Config>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let (params, cache_status) = get_app_parameters(upstream, cache).await?;
+    let stake_params = params
+        .stake_params
+        .as_ref()
+        .ok_or_else(|| Error::missing("stake_params"))?;
+    let epoch_duration = params
+        .sct_params
+        .as_ref()
+        .map(|sct_params| sct_params.epoch_duration);
+
+    let (bond_denom, denom_status) = get_bond_denom(upstream, cache, config).await;
+    let (unbonding_time, clock_status) = unbonding_duration(stake_params, upstream, cache).await;
+    let cache_status = cache_status.merge(denom_status).merge(clock_status);
+
+    let min_validator_stake = stake_params
+        .min_validator_stake
+        .as_ref()
+        .map(|amount| ((amount.hi as u128) << 64) | amount.lo as u128)
+        .unwrap_or_default();
+
+    let mut penumbra = json!({
+        "min_validator_stake": min_validator_stake.to_string(),
+        "base_reward_rate": decimal::from_ratio(stake_params.base_reward_rate as u128, EXCHANGE_RATE_SCALE),
+        "unbonding_delay": stake_params.unbonding_delay.to_string(),
+    });
+    if let Some(epoch_duration) = epoch_duration {
+        penumbra["epoch_duration"] = json!(epoch_duration.to_string());
+    }
 
-    let mut client = AppQueryServiceClient::new(channel);
-    let params: AppParameters = client
-        .app_parameters(tonic::Request::new(AppParametersRequest {}))
-        .await
-        .unwrap()
-        .into_inner()
-        .app_parameters
-        .unwrap()
-        .try_into()
-        .unwrap();
+    let mut result = json!({
+        "max_validators": stake_params.active_validator_limit,
+        "max_entries": 7,
+        "historical_entries": 10000,
+        "bond_denom": *bond_denom,
+        // Penumbra parameters with no Cosmos counterpart, like `extended=true` on validators.
+        "penumbra": penumbra,
+    });
+    if let Some(unbonding_time) = unbonding_time {
+        result["unbonding_time"] = json!(unbonding_time);
+    }
 
-    let stake_params = params.stake_params.unwrap();
+    Ok(Cached(json!({
+        "params": result,
+    }), cache_status))
+}
 
-    json!({
-        "params": {
-            "unbonding_time": "1814400s", // 21 days
-            "max_validators": stake_params.active_validator_limit,
-            "max_entries": 7,
-            "historical_entries": 10000,
-            "bond_denom": "upenumbra"
-        }
-    })
+async fn get_uptime(
+    upstream: &Upstream,
+    cache: &Cache,
+    identity_key: IdentityKey,
+) -> Result<(Arc<Uptime>, CacheStatus), Error> {
+    let upstream = upstream.clone();
+
+    cache.validator_uptime.slot(identity_key).get(|| async move {
+        upstream
+            .call(|upstream| async move {
+                let identity_key: ProtoIdentityKey = identity_key.into();
+                Ok(upstream
+                    .stake()
+                    .validator_uptime(ValidatorUptimeRequest {
+                        identity_key: Some(identity_key),
+                    })
+                    .await?)
+            })
+            .await?
+            .into_inner()
+            .uptime
+            .ok_or_else(|| Error::missing("uptime"))?
+            .try_into()
+            .map_err(Error::decode)
+    }).await
 }
 
+/// `jailed_until` the Cosmos SDK reports for tombstoned validators, which can never come back.
+const TOMBSTONED_JAILED_UNTIL: &str = "9999-12-31T23:59:59Z";
+
+/// `start_height` and `index_offset` of the uptime window.
+///
+/// Penumbra tracks uptime in a ring buffer indexed by height modulo the window length,
+/// which is what `index_offset` points into; `start_height` is where the current window
+/// begins.
+fn signing_window(uptime: Option<&Uptime>) -> (u64, u64) {
+    let Some(uptime) = uptime else {
+        return (0, 0);
+    };
 
-#[get("/cosmos/slashing/v1beta1/signing_infos/<identity_key>")]
-async fn signing_info(identity_key: &str, args: &State<Args>) -> Value {
-    let identity_key_parsed = identity_key.parse::<IdentityKey>().unwrap();
+    let window: ProtoUptime = uptime.clone().into();
+    let window_len = window.window_len as u64;
+    let start_height = window.as_of_block_height.saturating_sub(window_len.saturating_sub(1));
+    let index_offset = match window_len {
+        0 => 0,
+        window_len => window.as_of_block_height % window_len,
+    };
 
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
+    (start_height, index_offset)
+}
 
-    let mut client = StakeQueryServiceClient::new(channel);
-    let uptime: Uptime = client
-        .validator_uptime(ValidatorUptimeRequest {
-            identity_key: Some(identity_key_parsed.into()),
-        })
-        .await
-        .unwrap()
-        .into_inner()
-        .uptime
-        .unwrap()
-        .try_into()
-        .unwrap();
+/// `jailed_until` of a validator in `state`, `unbonding_time` being when a jailed
+/// validator's stake is released, if known.
+fn jailed_until(state: &ValidatorState, unbonding_time: Option<DateTime<Utc>>) -> String {
+    match state {
+        ValidatorState::Tombstoned => TOMBSTONED_JAILED_UNTIL.to_string(),
+        ValidatorState::Jailed => unbonding_time.unwrap_or_default().to_rfc3339_opts(SecondsFormat::Secs, true),
+        _ => DateTime::<Utc>::default().to_rfc3339_opts(SecondsFormat::Secs, true),
+    }
+}
 
-    let missed_blocks = uptime.num_missed_blocks();
+/// Maps a validator and its uptime to the Cosmos `ValidatorSigningInfo` object.
+/// Without an uptime, `missed_blocks_counter` is null.
+fn map_signing_info(
+    validator: &validator::Info,
+    uptime: Option<&Uptime>,
+    unbonding_time: Option<DateTime<Utc>>,
+    config: &Config,
+) -> Value {
+    let (start_height, index_offset) = signing_window(uptime);
 
     json!({
-        "val_signing_info": {
-            "address": identity_key,
-            "start_height": "0",
-            "index_offset": "0",
-            "jailed_until": "1970-01-01T00:00:00Z",
-            "tombstoned": false,
-            "missed_blocks_counter": missed_blocks.to_string()
-        }
+        "address": validator.validator.consensus_key.to_bech32(&config.chain.valcons_prefix),
+        "start_height": start_height.to_string(),
+        "index_offset": index_offset.to_string(),
+        "jailed_until": jailed_until(&validator.status.state, unbonding_time),
+        "tombstoned": validator.status.state == ValidatorState::Tombstoned,
+        "missed_blocks_counter": uptime.map(|uptime| uptime.num_missed_blocks().to_string()),
     })
 }
 
-#[get("/cosmos/slashing/v1beta1/signing_infos")]
-async fn signing_infos(args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
+/// Unbonding time of a jailed validator, for its `jailed_until`.
+async fn get_jailed_until(
+    upstream: &Upstream,
+    cache: &Cache,
+    validator: &validator::Info,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    let jailed_and_unbonding = validator.status.state == ValidatorState::Jailed
+        && matches!(validator.status.bonding_state, BondingState::Unbonding { .. });
+    if !jailed_and_unbonding {
+        return Ok(None);
+    }
 
-    let mut client = StakeQueryServiceClient::new(channel);
+    let (clock, _) = get_chain_clock(upstream, cache).await?;
+    get_unbonding_time(upstream, cache, &clock, validator).await
+}
 
-    let validators: Vec<validator::Info> = client
-        .validator_info(ValidatorInfoRequest {
-            show_inactive: true,
-            ..Default::default()
-        })
-        .await
-        .unwrap()
-        .into_inner()
-        .try_collect::<Vec<_>>()
-        .await
-        .unwrap()
-        .into_iter()
-        .map(TryInto::try_into)
-        .collect::<Result<Vec<validator::Info>, _>>()
-        .unwrap();
+#[get("/cosmos/slashing/v1beta1/signing_infos/<address>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn signing_info(address: &str, upstream: &State<Upstream>, cache: &State<Cache>, config: &State<Config>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let (validators, _) = get_validators(upstream, cache).await?;
+    let prefix = &config.chain.valcons_prefix;
+
+    // Cosmos tools look validators up by consensus address, Penumbra ones by identity key.
+    let validator_info = match address.parse::<IdentityKey>() {
+        Ok(identity_key) => match validators.iter().find(|validator| validator.validator.identity_key == identity_key) {
+            Some(validator) => validator.clone(),
+            None => get_validator(upstream, identity_key).await?,
+        },
+        Err(_) if address.starts_with(prefix.as_str()) => validators
+            .iter()
+            .find(|validator| validator.validator.consensus_key.to_bech32(prefix) == address)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("validator {} not found", address)))?,
+        Err(_) => return Err(Error::invalid_param("validator address", address)),
+    };
 
-    let mut result: Vec<_> = vec![];
-    for validator in validators {
-        let valcons = validator.validator.consensus_key.to_bech32("penumbravalcons");
-        let identity_key: ProtoIdentityKey = validator.validator.identity_key.into();
+    let (uptime, cache_status) = get_uptime(upstream, cache, validator_info.validator.identity_key).await?;
+    let jailed_until = get_jailed_until(upstream, cache, &validator_info).await?;
+
+    Ok(Cached(json!({
+        "val_signing_info": map_signing_info(&validator_info, Some(&*uptime), jailed_until, config),
+    }), cache_status))
+}
 
-        let uptime: Uptime = client
-            .validator_uptime(ValidatorUptimeRequest {
-                identity_key: Option::from(identity_key),
+#[get("/cosmos/slashing/v1beta1/signing_infos?<pagination>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn signing_infos(pagination: PageRequest, upstream: &State<Upstream>, cache: &State<Cache>, config: &State<Config>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let (validators, cache_status) = get_validators(upstream, cache).await?;
+
+    // Paginate before fetching uptimes so that a page only costs as many upstream calls as it has entries.
+    let page = pagination.paginate(
+        validators
+            .iter()
+            .map(|validator| {
+                let valcons = validator.validator.consensus_key.to_bech32(&config.chain.valcons_prefix);
+                (valcons.into_bytes(), validator)
             })
-            .await
-            .unwrap()
-            .into_inner()
-            .uptime
-            .unwrap()
-            .try_into()
-            .unwrap();
+            .collect(),
+    )?;
+
+    let uptimes: Vec<_> = stream::iter(page.items.iter())
+        .map(|validator| get_uptime(upstream, cache, validator.validator.identity_key))
+        .buffered(config.upstream.uptime_concurrency)
+        .collect()
+        .await;
+
+    // A validator whose uptime or jail term could not be fetched is still listed, with the
+    // missing fields left empty and an `error` telling why, rather than failing the whole
+    // page; only when no uptime could be fetched at all is the error returned.
+    let mut result: Vec<_> = vec![];
+    let mut fetched = false;
+    let mut last_error = None;
+    for (validator, uptime) in page.items.iter().zip(uptimes) {
+        let mut errors = vec![];
+
+        let uptime = match uptime {
+            Ok((uptime, _)) => {
+                fetched = true;
+                Some(uptime)
+            }
+            Err(error) => {
+                tracing::warn!(validator = %validator.validator.identity_key, error = %error.message(), "could not fetch validator uptime");
+                errors.push(format!("uptime unavailable: {}", error.message()));
+                last_error = Some(error);
+                None
+            }
+        };
+
+        let jailed_until = match get_jailed_until(upstream, cache, validator).await {
+            Ok(jailed_until) => jailed_until,
+            Err(error) => {
+                tracing::warn!(validator = %validator.validator.identity_key, error = %error.message(), "could not fetch validator jail term");
+                errors.push(format!("jailed_until unavailable: {}", error.message()));
+                None
+            }
+        };
 
-        result.push(json!({
-            "address": valcons,
-            "start_height": "0",
-            "index_offset": "0",
-            "jailed_until": "1970-01-01T00:00:00Z",
-            "tombstoned": false,
-            "missed_blocks_counter": uptime.num_missed_blocks()
-        }));
+        let mut info = map_signing_info(validator, uptime.as_deref(), jailed_until, config);
+        if !errors.is_empty() {
+            info["error"] = json!(errors.join("; "));
+        }
+
+        result.push(info);
     }
 
-    json!({
+    if let (false, Some(error)) = (fetched, last_error) {
+        return Err(error);
+    }
+
+    Ok(Cached(json!({
         "info": result,
-    })
+        "pagination": page.pagination(),
+    }), cache_status))
 }
 
-async fn get_sync_info(channel: Channel) -> SyncInfo {
-    let mut tendermint_client = TendermintProxyServiceClient::new(channel.clone());
-    let status_data: GetStatusResponse = tendermint_client
-        .get_status(GetStatusRequest { })
-        .await
-        .unwrap()
-        .into_inner();
+/// Time of the block at `height`, which never changes once the block exists.
+async fn get_block_timestamp(upstream: &Upstream, cache: &Cache, height: u64) -> Result<(Arc<i64>, CacheStatus), Error> {
+    let upstream = upstream.clone();
+
+    cache.block_time.slot(height).get(|| async move {
+        let block: GetBlockByHeightResponse = upstream
+            .call(|upstream| async move {
+                Ok(upstream
+                    .tendermint()
+                    .get_block_by_height(GetBlockByHeightRequest { height: height as i64 })
+                    .await?)
+            })
+            .await?
+            .into_inner();
+
+        Ok(block
+            .block
+            .and_then(|block| block.header)
+            .and_then(|header| header.time)
+            .ok_or_else(|| Error::missing("block time"))?
+            .seconds)
+    }).await
+}
 
-    status_data.sync_info.unwrap()
+
+#[get("/health")]
+fn health() -> Value {
+    json!({
+        "status": "ok",
+    })
 }
 
-async fn get_block_time(channel: Channel, latest_block_height: i64, latest_block_time: f64) -> f64 {
-    let mut tendermint_client = TendermintProxyServiceClient::new(channel.clone());
+/// Ready when the node queries are routed to answers, has caught up and keeps producing blocks.
+///
+/// Answers from the state of the last background health check rather than querying the
+/// node, so that load balancer probes get a quick answer even when the node is slow.
+#[get("/ready")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+fn ready(upstream: &State<Upstream>, config: &State<Config>, request_id: RequestId) -> status::Custom<Json<Value>> {
+    let node = upstream.current();
+    let health = node.health();
+
+    if !health.reachable {
+        return status::Custom(Status::ServiceUnavailable, Json(json!({
+            "ready": false,
+            "node": node.url,
+            "reachable": false,
+        })));
+    }
 
-    let older_block_data: GetBlockByHeightResponse = tendermint_client
-        .get_block_by_height(GetBlockByHeightRequest { height: latest_block_height - 100 })
-        .await
-        .unwrap()
-        .into_inner();
+    let block_age = health.latest_block_time.map(|time| Utc::now().timestamp() - time);
+    let stalled = block_age.map_or(true, |age| age > config.upstream.max_block_age as i64);
+    let ready = !health.catching_up && !stalled;
+
+    let code = if ready { Status::Ok } else { Status::ServiceUnavailable };
+
+    status::Custom(code, Json(json!({
+        "ready": ready,
+        "node": node.url,
+        "reachable": true,
+        "catching_up": health.catching_up,
+        "latest_block_height": health.latest_block_height.to_string(),
+        "latest_block_time": health.latest_block_time.and_then(|time| DateTime::from_timestamp(time, 0)),
+        "latest_block_age": block_age,
+        "max_block_age": config.upstream.max_block_age,
+    })))
+}
 
-    let older_block_header = older_block_data.block.unwrap().header.unwrap();
-    let older_block_height = older_block_header.height;
-    let older_block_time: f64 = older_block_header.time.unwrap().seconds as f64;
 
-    let time_between_blocks = latest_block_time - older_block_time;
-    let blocks_diff = latest_block_height - older_block_height;
+/// Summarizes the transaction plan of a community pool spend: the plan itself can be large,
+/// alerting only needs to know what it does at a glance.
+fn map_transaction_plan(type_url: &str, plan: &[u8]) -> Value {
+    match TransactionPlan::decode(plan) {
+        Ok(plan) => {
+            let parameters = plan.transaction_parameters.unwrap_or_default();
+            json!({
+                "type_url": type_url,
+                "actions": plan.actions.len(),
+                "expiry_height": parameters.expiry_height.to_string(),
+                "chain_id": parameters.chain_id,
+            })
+        }
+        Err(error) => {
+            tracing::warn!(%error, "undecodable community pool spend transaction plan");
+            json!({ "type_url": type_url })
+        }
+    }
+}
 
-    time_between_blocks / (blocks_diff as f64)
+/// Renames the camelCase fields of the protobuf JSON mapping to the snake_case used
+/// everywhere else in the responses.
+fn snake_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (metrics::snake_case(&key), snake_case_keys(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(snake_case_keys).collect()),
+        value => value,
+    }
 }
 
+/// Maps the proposal payload to its proto type, e.g.
+/// `penumbra.core.component.governance.v1.Proposal.Emergency`, along with its fields.
+fn map_proposal_content(proposal: Proposal) -> Result<Value, Error> {
+    let mut content = json!({
+        "title": proposal.title,
+        "description": proposal.description,
+    });
+
+    let (kind, fields) = match proposal.payload {
+        Some(ProposalPayload::Signaling(signaling)) => ("Signaling", json!({
+            "commit": signaling.commit,
+        })),
+        Some(ProposalPayload::Emergency(emergency)) => ("Emergency", json!({
+            "halt_chain": emergency.halt_chain,
+        })),
+        Some(ProposalPayload::ParameterChange(change)) => ("ParameterChange", json!({
+            "parameter_change": snake_case_keys(serde_json::to_value(&change).map_err(Error::decode)?),
+        })),
+        Some(ProposalPayload::CommunityPoolSpend(spend)) => ("CommunityPoolSpend", json!({
+            "transaction_plan": spend
+                .transaction_plan
+                .map(|plan| map_transaction_plan(&plan.type_url, &plan.value))
+                .unwrap_or_default(),
+        })),
+        Some(ProposalPayload::UpgradePlan(upgrade)) => ("UpgradePlan", json!({
+            "height": upgrade.height.to_string(),
+        })),
+        Some(ProposalPayload::FreezeIbcClient(freeze)) => ("FreezeIbcClient", json!({
+            "client_id": freeze.client_id,
+        })),
+        Some(ProposalPayload::UnfreezeIbcClient(unfreeze)) => ("UnfreezeIbcClient", json!({
+            "client_id": unfreeze.client_id,
+        })),
+        // Without a payload there is no type to report.
+        None => return Ok(content),
+    };
+
+    content["@type"] = json!(format!("penumbra.core.component.governance.v1.Proposal.{}", kind));
+    if let (Some(content), Value::Object(fields)) = (content.as_object_mut(), fields) {
+        content.extend(fields);
+    }
+
+    Ok(content)
+}
 
 fn map_proposal(
     proposal_id: u64,
     proposal: Proposal,
     state: ProposalState,
-    latest_block_height: i64,
-    latest_block_time: f64,
+    clock: &ChainClock,
     start_block_height: u64,
     end_block_height: u64,
-    block_time: f64,
-) -> Value {
+) -> Result<Value, Error> {
     let state = match state {
         ProposalState::Voting(_) => "PROPOSAL_STATUS_VOTING_PERIOD",
         ProposalState::Finished(Finished { outcome: Some(value) }) => {
-            match value.outcome.unwrap() {
-                Outcome::Passed(_) => "PROPOSAL_STATUS_PASSED",
-                Outcome::Failed(_) => "PROPOSAL_STATUS_REJECTED",
+            match value.outcome {
+                Some(Outcome::Passed(_)) => "PROPOSAL_STATUS_PASSED",
+                Some(Outcome::Failed(_)) => "PROPOSAL_STATUS_REJECTED",
                 _ => "ProposalStatus_PROPOSAL_STATUS_UNSPECIFIED"
             }
         },
         _ => "ProposalStatus_PROPOSAL_STATUS_UNSPECIFIED"
     };
 
-    let voting_start = latest_block_time
-        - ((latest_block_height - (start_block_height as i64)) as f64) * block_time;
-    let voting_end = latest_block_time
-        - ((latest_block_height - (end_block_height as i64)) as f64) * block_time;
-
-    json!({
+    Ok(json!({
         "proposal_id": proposal_id.to_string(),
-        "content": {
-            "@type": "penumbra.core.component.governance.v1.Signaling",
-            "title": proposal.title,
-            "description": proposal.description,
-        },
+        "content": map_proposal_content(proposal)?,
         "status": state,
         "final_tally_result": {
             "yes": "0",
@@ -436,242 +1048,419 @@ fn map_proposal(
         "submit_time": "1970-01-01T00:00:00.000Z",
         "deposit_end_time": "1970-01-01T00:00:00.000Z",
         "total_deposit": [],
-        "voting_start_time": DateTime::from_timestamp(voting_start as i64, 0),
-        "voting_end_time": DateTime::from_timestamp(voting_end as i64, 0),
-    })
+        "voting_start_time": clock.estimate(start_block_height),
+        "voting_end_time": clock.estimate(end_block_height),
+    }))
 }
 
 #[get("/cosmos/gov/v1beta1/proposals/<proposal_id>/tally")]
-async fn proposal_tally(proposal_id: u64, args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
-
-    let mut client = GovernanceQueryServiceClient::new(channel.clone());
-    let tallies: Vec<AllTalliedDelegatorVotesForProposalResponse> = client
-        .all_tallied_delegator_votes_for_proposal(AllTalliedDelegatorVotesForProposalRequest {  proposal_id: proposal_id})
-        .await
-        .unwrap()
-        .into_inner()
-        .try_collect::<Vec<_>>()
-        .await
-        .unwrap();
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn proposal_tally(proposal_id: Result<u64, &str>, upstream: &State<Upstream>, request_id: RequestId) -> Result<Value, Error> {
+    let proposal_id = proposal_id.map_err(|value| Error::invalid_param("proposal id", value))?;
+
+    let tallies: Vec<AllTalliedDelegatorVotesForProposalResponse> = upstream
+        .call(|upstream| async move {
+            upstream
+                .governance()
+                .all_tallied_delegator_votes_for_proposal(AllTalliedDelegatorVotesForProposalRequest {  proposal_id: proposal_id})
+                .await?
+                .into_inner()
+                .try_collect::<Vec<_>>()
+                .await
+                .map_err(Error::from)
+        })
+        .await?;
 
     let mut total = penumbra_governance::Tally::default();
 
     for tally in tallies {
-        total += tally.tally.unwrap().into();
+        total += tally.tally.ok_or_else(|| Error::missing("tally"))?.into();
     }
 
-    json!({
+    Ok(json!({
         "tally": {
             "yes": total.yes().to_string(),
             "no": total.no().to_string(),
             "abstain": total.abstain().to_string(),
             "no_with_veto": "0"
         },
-    })
+    }))
 }
 
 #[get("/cosmos/gov/v1beta1/proposals/<proposal_id>")]
-async fn proposal(proposal_id: u64, args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn proposal(proposal_id: Result<u64, &str>, upstream: &State<Upstream>, cache: &State<Cache>, request_id: RequestId) -> Result<Value, Error> {
+    let proposal_id = proposal_id.map_err(|value| Error::invalid_param("proposal id", value))?;
 
-    let mut client = GovernanceQueryServiceClient::new(channel.clone());
-    let proposal_data: ProposalDataResponse = client
-        .proposal_data(ProposalDataRequest { proposal_id: proposal_id })
-        .await
-        .unwrap()
+    let proposal_data: ProposalDataResponse = upstream
+        .call(|upstream| async move {
+            Ok(upstream.governance().proposal_data(ProposalDataRequest { proposal_id: proposal_id }).await?)
+        })
+        .await?
         .into_inner();
 
-    let sync_info = get_sync_info(channel.clone()).await;
-    let latest_block_height: i64 = (sync_info.latest_block_height) as i64;
-    let latest_block_time: f64 = sync_info.latest_block_time.unwrap().seconds as f64;
-    let block_time = get_block_time(channel.clone(), latest_block_height, latest_block_time).await;
+    let (clock, _) = get_chain_clock(upstream, cache).await?;
 
     let proposal = map_proposal(
         proposal_id,
-        proposal_data.proposal.unwrap(),
-        proposal_data.state.unwrap().state.unwrap(),
-        latest_block_height,
-        latest_block_time,
+        proposal_data.proposal.ok_or_else(|| Error::missing("proposal"))?,
+        proposal_data
+            .state
+            .and_then(|state| state.state)
+            .ok_or_else(|| Error::missing("proposal state"))?,
+        &clock,
         proposal_data.start_block_height,
         proposal_data.end_block_height,
-        block_time,
-    );
+    )?;
 
-    json!({
+    Ok(json!({
         "proposal": proposal,
-    })
+    }))
 }
 
-#[get("/cosmos/gov/v1beta1/proposals")]
-async fn proposals(args: &State<Args>) -> Value {
-    let channel = Channel::from_shared(args.node.to_string())
-        .unwrap()
-        .tls_config(ClientTlsConfig::new())
-        .unwrap()
-        .connect()
-        .await
-        .unwrap();
-
-    let mut client = GovernanceQueryServiceClient::new(channel.clone());
-
-    let proposals: Vec<ProposalListResponse> = client
-        .proposal_list(ProposalListRequest { inactive: true })
-        .await
-        .unwrap()
-        .into_inner()
-        .try_collect::<Vec<_>>()
-        .await
-        .unwrap();
-
-    let sync_info = get_sync_info(channel.clone()).await;
-    let latest_block_height: i64 = (sync_info.latest_block_height) as i64;
-    let latest_block_time: f64 = sync_info.latest_block_time.unwrap().seconds as f64;
-    let block_time = get_block_time(channel.clone(), latest_block_height, latest_block_time).await;
+#[get("/cosmos/gov/v1beta1/proposals?<pagination>")]
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn proposals(pagination: PageRequest, upstream: &State<Upstream>, cache: &State<Cache>, request_id: RequestId) -> Result<Cached<Value>, Error> {
+    let (proposals, cache_status) = get_proposals(upstream, cache).await?;
+
+    let (clock, clock_status) = get_chain_clock(upstream, cache).await?;
+    let cache_status = cache_status.merge(clock_status);
+
+    let page = pagination.paginate(
+        proposals
+            .iter()
+            .map(|proposal| {
+                let proposal_id = proposal
+                    .proposal
+                    .as_ref()
+                    .ok_or_else(|| Error::missing("proposal"))?
+                    .id;
+                Ok((proposal_id.to_be_bytes().to_vec(), proposal))
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+    )?;
 
     let mut response: Vec<Value> = vec![];
 
-    for proposal in proposals {
-        let proposal_unwrapped = proposal.proposal.unwrap();
+    for proposal in page.items.iter() {
+        let proposal_unwrapped = proposal
+            .proposal
+            .clone()
+            .ok_or_else(|| Error::missing("proposal"))?;
         let proposal_mapped = map_proposal(
             proposal_unwrapped.id,
             proposal_unwrapped,
-            proposal.state.unwrap().state.unwrap(),
-            latest_block_height,
-            latest_block_time,
+            proposal
+                .state
+                .clone()
+                .and_then(|state| state.state)
+                .ok_or_else(|| Error::missing("proposal state"))?,
+            &clock,
             proposal.start_block_height,
             proposal.end_block_height,
-            block_time,
-        );
+        )?;
 
         response.push(proposal_mapped);
     }
 
-    json!({
+    Ok(Cached(json!({
         "proposals": response,
-        "pagination": {
-            "next_key": null,
-            "total": response.len().to_string(),
-        }
-    })
+        "pagination": page.pagination(),
+    }), cache_status))
 }
 
 
-async fn get_vote(voter: &str, proposal_id: u64, args: &State<Args>) -> status::Custom<Json<Value>> {
-  let channel = Channel::from_shared(args.node.to_string())
-      .unwrap()
-      .tls_config(ClientTlsConfig::new())
-      .unwrap()
-      .connect()
-      .await
-      .unwrap();
-
-  let mut client = GovernanceQueryServiceClient::new(channel.clone());
-  let votes_data: Vec<ValidatorVotesResponse> = client
-      .validator_votes(ValidatorVotesRequest { proposal_id: proposal_id })
-      .await
-      .unwrap()
-      .into_inner()
-      .try_collect::<Vec<_>>()
-      .await
-      .unwrap();
-
-  let validator_vote = votes_data
-      .iter()
-      .find(|&vote| {
-          let identity: IdentityKey = vote.clone().identity_key.unwrap().try_into().unwrap();
-          identity.to_string() == voter
-      });
-
-  let response = match validator_vote {
-      None => {
-          status::Custom(rocket::http::Status::BadRequest, Json(json!({
-              "code": 3,
-              "message": format!("voter: {} not found for proposal: {}", voter, proposal_id),
-              "details": []
-          })))
-      },
-      Some(vote) => {
-          match &vote.vote {
-              None => {
-                  status::Custom(rocket::http::Status::BadRequest, Json(json!({
-                      "code": 3,
-                      "message": format!("voter: {} not found for proposal: {}", voter, proposal_id),
-                      "details": []
-                  })))
-              },
-              Some(option) => {
-                  let vote = match option.vote {
-                      3 => "VOTE_OPTION_NO",
-                      2 => "VOTE_OPTION_YES",
-                      1 => "VOTE_OPTION_ABSTAIN",
-                      _ => "VOTE_OPTION_UNSPECIFIED"
-                  };
-
-                  status::Custom(rocket::http::Status::Ok, Json(json!({
-                      "vote": {
-                        "proposal_id": proposal_id.to_string(),
-                        "voter": voter,
-                        "option": vote,
-                        "options": [
-                          {
-                            "option": vote,
-                            "weight": "1.000000000000000000"
-                          }
-                        ]
-                      }
-                  })))
-              }
-          }
+async fn get_vote(voter: &str, proposal_id: u64, upstream: &Upstream) -> Result<Value, Error> {
+  let votes_data: Vec<ValidatorVotesResponse> = upstream
+      .call(|upstream| async move {
+          upstream
+              .governance()
+              .validator_votes(ValidatorVotesRequest { proposal_id: proposal_id })
+              .await?
+              .into_inner()
+              .try_collect::<Vec<_>>()
+              .await
+              .map_err(Error::from)
+      })
+      .await?;
+
+  let mut validator_vote = None;
+  for vote in &votes_data {
+      let identity: IdentityKey = vote
+          .identity_key
+          .clone()
+          .ok_or_else(|| Error::missing("identity_key"))?
+          .try_into()
+          .map_err(Error::decode)?;
+
+      if identity.to_string() == voter {
+          validator_vote = Some(vote);
+          break;
       }
+  }
+
+  let option = validator_vote
+      .and_then(|vote| vote.vote.as_ref())
+      .ok_or_else(|| Error::InvalidArgument(
+          format!("voter: {} not found for proposal: {}", voter, proposal_id)
+      ))?;
+
+  let vote = match option.vote {
+      3 => "VOTE_OPTION_NO",
+      2 => "VOTE_OPTION_YES",
+      1 => "VOTE_OPTION_ABSTAIN",
+      _ => "VOTE_OPTION_UNSPECIFIED"
   };
 
-  response
+  Ok(json!({
+      "vote": {
+        "proposal_id": proposal_id.to_string(),
+        "voter": voter,
+        "option": vote,
+        "options": [
+          {
+            "option": vote,
+            "weight": "1.000000000000000000"
+          }
+        ]
+      }
+  }))
 }
 
 #[get("/cosmos/gov/v1beta1/proposals/<proposal_id>/votes/<voter>")]
-async fn proposal_vote(proposal_id: u64, voter: &str, args: &State<Args>) -> status::Custom<Json<Value>> {
-    get_vote(voter, proposal_id, args).await
+#[tracing::instrument(skip_all, fields(request_id = %request_id))]
+async fn proposal_vote(proposal_id: Result<u64, &str>, voter: &str, upstream: &State<Upstream>, request_id: RequestId) -> Result<Value, Error> {
+    let proposal_id = proposal_id.map_err(|value| Error::invalid_param("proposal id", value))?;
+
+    get_vote(voter, proposal_id, upstream).await
+}
+
+
+/// State shared by the handlers. It is built once and handed to every launch, so that
+/// node health, breakers, cached entries and rate limits survive certificate reloads.
+struct Services {
+    upstream: Upstream,
+    cache: Cache,
+    limiter: Option<RateLimiter>,
+}
+
+impl Services {
+    fn new(config: &Config) -> Result<Self, String> {
+        let upstream = Upstream::new(&config.upstream)
+            .map_err(|error| format!("invalid upstream node configuration: {}", error))?;
+
+        Ok(Self {
+            upstream,
+            cache: Cache::new(&config.cache),
+            limiter: RateLimiter::new(&config.rate_limit)?,
+        })
+    }
 }
 
+fn rocket(figment: Figment, config: &Config, services: &Services) -> Rocket<Build> {
+    let toggles = &config.routes;
+    let routes = [
+        (toggles.validators, routes![validators]),
+        (toggles.validator_by_addr, routes![validator_by_addr]),
+        (toggles.staking_params, routes![staking_params]),
+        (toggles.slashing_params, routes![slashing_params]),
+        (toggles.pool, routes![pool]),
+        (toggles.signing_info, routes![signing_info]),
+        (toggles.signing_infos, routes![signing_infos]),
+        (toggles.proposals, routes![proposals]),
+        (toggles.proposal, routes![proposal]),
+        (toggles.proposal_vote, routes![proposal_vote]),
+        (toggles.proposal_tally, routes![proposal_tally]),
+        (toggles.metrics, routes![metrics::metrics]),
+        (toggles.health, routes![health]),
+        (toggles.ready, routes![ready]),
+        (config.cors.enabled, routes![cors::preflight]),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .flat_map(|(_, routes)| routes)
+    .collect::<Vec<_>>();
+
+    let rocket = match &services.limiter {
+        Some(limiter) => rocket::custom(figment).manage(limiter.clone()),
+        None => rocket::custom(figment),
+    };
+    let routes = upstream::track(ratelimit::limit(routes, &config.rate_limit));
+
+    let rocket = match config.cors.enabled {
+        true => rocket.attach(cors::fairing(&config.cors)),
+        false => rocket,
+    };
+
+    rocket
+        .manage(services.upstream.clone())
+        .attach(Upstream::fairing())
+        .attach(metrics::fairing())
+        .attach(logging::fairing())
+        .manage(services.cache.clone())
+        .manage(config.clone())
+        .register("/", catchers![error::default_catcher])
+        .mount("/", routes)
+}
 
-#[launch]
-fn rocket() -> _ {
+#[rocket::main]
+async fn main() -> Result<(), rocket::Error> {
     let args = Args::parse();
 
-    let ip_addr: IpAddr = args.bind.parse().expect("Invalid IP address format");
-
-    rocket::build()
-        .configure(rocket::Config::figment()
-                   .merge(("port", args.port))
-                   .merge(("address", ip_addr))
-        )
-        .manage(args)
-        .mount(
-            "/",
-            routes![
-                validators,
-                staking_params,
-                slashing_params,
-                pool,
-                signing_info,
-                signing_infos,
-                proposals,
-                proposal,
-                proposal_vote,
-                proposal_tally,
-            ],
-        )
+    let (figment, config) = match config::figment(&args)
+        .and_then(|figment| Config::from_figment(&figment).map(|config| (figment, config)))
+    {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("Invalid configuration: {}", error);
+            process::exit(1);
+        }
+    };
+
+    if let Err(error) = logging::init(&config.log) {
+        eprintln!("Could not set up logging: {}", error);
+        process::exit(1);
+    }
+
+    let services = match Services::new(&config) {
+        Ok(services) => services,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let figment = tls::reload_grace(figment, &config);
+    let mut reloaded = false;
+    loop {
+        let result = match rocket(figment.clone(), &config, &services).ignite().await {
+            Ok(rocket) => {
+                let reloading = tls::watch(&figment, &config, rocket.shutdown());
+                rocket.launch().await.map(|_| reloading.load(Ordering::SeqCst))
+            }
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(true) => reloaded = true,
+            Ok(false) => return Ok(()),
+            // The certificates were checked before shutting down for the reload, so this
+            // should be transient (e.g. the port not released yet): keep trying rather than
+            // letting a certificate renewal take the service down.
+            Err(error) if reloaded => {
+                tracing::error!(%error, "could not relaunch after reloading the certificates, retrying");
+                rocket::tokio::time::sleep(Duration::from_secs(config.tls_reload_interval)).await;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn bond_statuses() {
+        assert_eq!(parse_bond_statuses(&[]).unwrap(), None);
+        assert_eq!(
+            parse_bond_statuses(&values(&["BOND_STATUS_BONDED,BOND_STATUS_UNBONDING", "BOND_STATUS_UNBONDED"])).unwrap(),
+            Some(vec!["BOND_STATUS_BONDED", "BOND_STATUS_UNBONDING", "BOND_STATUS_UNBONDED"]),
+        );
+        assert_eq!(
+            parse_bond_statuses(&values(&[" BOND_STATUS_BONDED , "])).unwrap(),
+            Some(vec!["BOND_STATUS_BONDED"]),
+        );
+    }
+
+    #[test]
+    fn unspecified_bond_status_means_all() {
+        assert_eq!(parse_bond_statuses(&values(&["BOND_STATUS_UNSPECIFIED"])).unwrap(), None);
+        assert_eq!(
+            parse_bond_statuses(&values(&["BOND_STATUS_BONDED", "BOND_STATUS_UNSPECIFIED"])).unwrap(),
+            None,
+        );
+    }
+
+    #[test]
+    fn invalid_bond_statuses() {
+        // Every value is checked, even once UNSPECIFIED has widened the filter to all.
+        assert!(matches!(
+            parse_bond_statuses(&values(&["BOND_STATUS_UNSPECIFIED,BOND_STATUS_JAILED"])),
+            Err(Error::InvalidArgument(_)),
+        ));
+        // The Cosmos enum names are matched exactly.
+        assert!(matches!(parse_bond_statuses(&values(&["bond_status_bonded"])), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn empty_values_are_ignored() {
+        assert_eq!(parse_bond_statuses(&values(&["", ","])).unwrap(), None);
+        assert_eq!(parse_validator_states(&values(&["", " , "])).unwrap(), None);
+    }
+
+    #[test]
+    fn validator_states() {
+        assert_eq!(parse_validator_states(&[]).unwrap(), None);
+        assert_eq!(
+            parse_validator_states(&values(&["Jailed,TOMBSTONED", "active"])).unwrap(),
+            Some(vec!["jailed", "tombstoned", "active"]),
+        );
+        assert!(matches!(
+            parse_validator_states(&values(&["active,unbonding"])),
+            Err(Error::InvalidArgument(_)),
+        ));
+    }
+
+    #[test]
+    fn signing_window_without_uptime() {
+        assert_eq!(signing_window(None), (0, 0));
+    }
+
+    #[test]
+    fn signing_window_of_an_empty_window() {
+        assert_eq!(signing_window(Some(&Uptime::new(42, 0))), (42, 0));
+    }
+
+    #[test]
+    fn signing_window_before_a_full_window() {
+        // The window would start before genesis.
+        assert_eq!(signing_window(Some(&Uptime::new(5, 10))), (0, 5));
+    }
+
+    #[test]
+    fn signing_window_of_a_full_window() {
+        assert_eq!(signing_window(Some(&Uptime::new(105, 10))), (96, 5));
+    }
+
+    #[test]
+    fn tombstoned_validators_are_jailed_forever() {
+        let unbonding_time = DateTime::from_timestamp(1_700_000_000, 0);
+
+        assert_eq!(jailed_until(&ValidatorState::Tombstoned, unbonding_time), TOMBSTONED_JAILED_UNTIL);
+        assert_eq!(jailed_until(&ValidatorState::Tombstoned, None), TOMBSTONED_JAILED_UNTIL);
+    }
+
+    #[test]
+    fn jailed_validators_are_jailed_until_they_unbond() {
+        let unbonding_time = DateTime::from_timestamp(1_700_000_000, 0);
+
+        assert_eq!(jailed_until(&ValidatorState::Jailed, unbonding_time), "2023-11-14T22:13:20Z");
+        assert_eq!(jailed_until(&ValidatorState::Jailed, None), "1970-01-01T00:00:00Z");
+        assert_eq!(jailed_until(&ValidatorState::Active, unbonding_time), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn flags() {
+        assert!(!parse_flag("extended", None).unwrap());
+        assert!(parse_flag("extended", Some("")).unwrap());
+        assert!(parse_flag("extended", Some("TRUE")).unwrap());
+        assert!(parse_flag("extended", Some("1")).unwrap());
+        assert!(!parse_flag("extended", Some("false")).unwrap());
+        assert!(matches!(parse_flag("extended", Some("yes")), Err(Error::InvalidArgument(_))));
+    }
 }
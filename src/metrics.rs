@@ -0,0 +1,225 @@
+use std::sync::LazyLock;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Encoder,
+    HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use rocket::fairing::AdHoc;
+use rocket::http::ContentType;
+use tonic::body::BoxBody;
+use tonic::codegen::http;
+use tonic::transport::{Body, Channel};
+use tower::Service;
+use tracing::Instrument;
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "lcd_http_requests_total",
+        "HTTP requests served, by route and status code",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+static HTTP_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "lcd_http_request_duration_seconds",
+        "Time spent serving HTTP requests, by route",
+        &["method", "route"]
+    )
+    .unwrap()
+});
+
+static UPSTREAM_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "lcd_upstream_call_duration_seconds",
+        "Time until the upstream node answered a gRPC call, by service and method",
+        &["service", "method"]
+    )
+    .unwrap()
+});
+
+static UPSTREAM_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "lcd_upstream_call_errors_total",
+        "Failed upstream gRPC calls, by service, method and gRPC status code",
+        &["service", "method", "code"]
+    )
+    .unwrap()
+});
+
+static CACHE_LOOKUPS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "lcd_cache_lookups_total",
+        "Cache lookups, by query and result as reported in the X-Cache header",
+        &["query", "result"]
+    )
+    .unwrap()
+});
+
+static UPSTREAM_HEIGHT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "lcd_upstream_latest_block_height",
+        "Latest block height reported by each upstream node",
+        &["node"]
+    )
+    .unwrap()
+});
+
+static UPSTREAM_UP: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "lcd_upstream_up",
+        "Whether the upstream node answered its last health check and is not catching up",
+        &["node"]
+    )
+    .unwrap()
+});
+
+pub fn record_cache_lookup(query: &str, result: &str) {
+    CACHE_LOOKUPS.with_label_values(&[query, result]).inc();
+}
+
+pub fn record_latest_height(node: &str, latest_block_height: u64) {
+    UPSTREAM_HEIGHT.with_label_values(&[node]).set(latest_block_height as i64);
+}
+
+pub fn record_node_health(node: &str, usable: bool, latest_block_height: u64) {
+    UPSTREAM_UP.with_label_values(&[node]).set(usable as i64);
+    record_latest_height(node, latest_block_height);
+}
+
+/// Splits a gRPC path such as `/penumbra.core.component.stake.v1.QueryService/ValidatorInfo`
+/// into a short service name (`stake`) and the method name.
+fn grpc_labels(path: &str) -> (String, String) {
+    let (service, method) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .unwrap_or((path, ""));
+
+    let service = match service.split('.').collect::<Vec<_>>().as_slice() {
+        ["penumbra", "core", "component", component, ..] => component.to_string(),
+        ["penumbra", "core", component, ..] => component.to_string(),
+        ["penumbra", "util", "tendermint_proxy", ..] => String::from("tendermint_proxy"),
+        _ => service.to_string(),
+    };
+
+    (service, method.to_string())
+}
+
+/// `ValidatorInfo` -> `validator_info`, matching the client method names in the logs.
+pub fn snake_case(method: &str) -> String {
+    let mut name = String::with_capacity(method.len() + 4);
+    for (index, c) in method.chars().enumerate() {
+        if c.is_ascii_uppercase() && index > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+
+    name
+}
+
+/// Channel wrapper recording latency and failures of every upstream gRPC call, each in
+/// its own `upstream` span nested in the span of the request that made it.
+///
+/// Latency is measured until the response headers arrive, which for the streaming
+/// queries is the first message rather than the end of the stream.
+#[derive(Clone)]
+pub struct MeteredChannel<S = Channel> {
+    inner: S,
+}
+
+impl<S> MeteredChannel<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Service<http::Request<BoxBody>> for MeteredChannel<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<Body>, Error = tonic::transport::Error>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = tonic::transport::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let (service, method) = grpc_labels(request.uri().path());
+        let span = tracing::info_span!("upstream", service = %service, method = %snake_case(&method));
+        let started = Instant::now();
+        let response = span.in_scope(|| self.inner.call(request));
+
+        Box::pin(async move {
+            let response = response.await;
+
+            UPSTREAM_DURATION
+                .with_label_values(&[&service, &method])
+                .observe(started.elapsed().as_secs_f64());
+
+            // Errors raised before any message is sent come back as trailers-only
+            // responses, with the status in the headers.
+            let code = match &response {
+                Ok(response) => response
+                    .headers()
+                    .get("grpc-status")
+                    .and_then(|code| code.to_str().ok())
+                    .filter(|code| *code != "0")
+                    .map(str::to_string),
+                Err(_) => Some((tonic::Code::Unavailable as i32).to_string()),
+            };
+
+            match code {
+                Some(code) => {
+                    tracing::warn!(code = %code, elapsed_ms = started.elapsed().as_secs_f64() * 1000.0, "upstream call failed");
+                    UPSTREAM_ERRORS.with_label_values(&[&service, &method, &code]).inc();
+                }
+                None => tracing::debug!(elapsed_ms = started.elapsed().as_secs_f64() * 1000.0, "upstream call answered"),
+            }
+
+            response
+        }.instrument(span))
+    }
+}
+
+/// Records the count, status and duration of every HTTP request.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_ignite("Metrics", |rocket| async {
+        rocket
+            .attach(AdHoc::on_request("Request start time", |request, _| Box::pin(async move {
+                request.local_cache(Instant::now);
+            })))
+            .attach(AdHoc::on_response("Request metrics", |request, response| Box::pin(async move {
+                let started = request.local_cache(Instant::now);
+                let method = request.method().as_str();
+                let route = request
+                    .route()
+                    .map(|route| route.uri.origin.path().as_str())
+                    .unwrap_or("unmatched");
+
+                HTTP_REQUESTS
+                    .with_label_values(&[method, route, &response.status().code.to_string()])
+                    .inc();
+                HTTP_DURATION
+                    .with_label_values(&[method, route])
+                    .observe(started.elapsed().as_secs_f64());
+            })))
+    })
+}
+
+#[get("/metrics")]
+pub fn metrics() -> (ContentType, String) {
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .unwrap();
+
+    (ContentType::Plain, String::from_utf8(buffer).unwrap())
+}
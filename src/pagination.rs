@@ -0,0 +1,306 @@
+use rocket::serde::json::{json, Value};
+
+use crate::error::Error;
+
+/// Same default page size as the Cosmos SDK.
+const DEFAULT_LIMIT: u64 = 100;
+
+/// Cosmos `pagination.*` query parameters.
+///
+/// `offset` and `limit` are parsed in [`PageRequest::paginate`]: as `Option<u64>`, Rocket
+/// would silently turn a malformed value into `None` and serve the first page.
+#[derive(FromForm, Debug, Default)]
+pub struct PageRequest {
+    key: Option<String>,
+    offset: Option<String>,
+    limit: Option<String>,
+    count_total: bool,
+    reverse: bool,
+}
+
+/// One page of results along with what is needed to render the `pagination` object.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    next_key: Option<Vec<u8>>,
+    total: Option<usize>,
+}
+
+fn parse_number(name: &str, value: &Option<String>) -> Result<Option<u64>, Error> {
+    value
+        .as_deref()
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().map_err(|_| Error::invalid_param(name, value)))
+        .transpose()
+}
+
+impl PageRequest {
+    /// Sorts `items` by their key and returns the requested page.
+    ///
+    /// Keys only need to be unique and stable between requests; `next_key` is the key of the
+    /// first item of the following page, base64-encoded, and the next request starts from it.
+    pub fn paginate<T>(&self, mut items: Vec<(Vec<u8>, T)>) -> Result<Page<T>, Error> {
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if self.reverse {
+            items.reverse();
+        }
+
+        let offset = parse_number("pagination offset", &self.offset)?;
+        let limit = parse_number("pagination limit", &self.limit)?;
+        let total = items.len();
+
+        let start = match (&self.key, offset) {
+            (Some(_), Some(_)) => {
+                return Err(Error::InvalidArgument(
+                    "paginate: invalid request, either offset or key is expected, got both".to_string(),
+                ));
+            }
+            (Some(key), None) => {
+                let key = base64::decode(key).map_err(|_| Error::invalid_param("pagination key", key))?;
+
+                // The item the key points to may have disappeared since, so look for the first
+                // item at or after it in iteration order rather than for an exact match.
+                items
+                    .iter()
+                    .position(|(item_key, _)| {
+                        if self.reverse {
+                            *item_key <= key
+                        } else {
+                            *item_key >= key
+                        }
+                    })
+                    .unwrap_or(total)
+            }
+            (None, offset) => (offset.unwrap_or(0) as usize).min(total),
+        };
+
+        let limit = match limit {
+            None | Some(0) => DEFAULT_LIMIT,
+            Some(limit) => limit,
+        } as usize;
+        let end = start.saturating_add(limit).min(total);
+
+        let next_key = items.get(end).map(|(key, _)| key.clone());
+        let items = items
+            .into_iter()
+            .skip(start)
+            .take(end - start)
+            .map(|(_, item)| item)
+            .collect();
+
+        Ok(Page {
+            items,
+            next_key,
+            total: self.count_total.then_some(total),
+        })
+    }
+}
+
+impl<T> Page<T> {
+    /// The Cosmos `pagination` response object.
+    pub fn pagination(&self) -> Value {
+        json!({
+            "next_key": self.next_key.as_ref().map(base64::encode),
+            "total": self.total.unwrap_or(0).to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(keys: &[&str]) -> Vec<(Vec<u8>, String)> {
+        keys.iter().map(|key| (key.as_bytes().to_vec(), key.to_string())).collect()
+    }
+
+    fn key(value: &str) -> Option<String> {
+        Some(base64::encode(value))
+    }
+
+    #[test]
+    fn sorts_and_applies_the_default_limit() {
+        let keys: Vec<String> = (0..150).map(|index| format!("{:03}", index)).collect();
+        let keys: Vec<&str> = keys.iter().rev().map(String::as_str).collect();
+
+        let page = PageRequest::default().paginate(items(&keys)).unwrap();
+
+        assert_eq!(page.items.len(), DEFAULT_LIMIT as usize);
+        assert_eq!(page.items[0], "000");
+        assert_eq!(page.items[99], "099");
+        assert_eq!(page.next_key, Some(b"100".to_vec()));
+    }
+
+    #[test]
+    fn zero_limit_means_default() {
+        let request = PageRequest {
+            limit: Some("0".to_string()),
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b"])).unwrap();
+
+        assert_eq!(page.items, ["a", "b"]);
+    }
+
+    #[test]
+    fn no_next_key_on_the_last_page() {
+        let request = PageRequest {
+            limit: Some("2".to_string()),
+            offset: Some("1".to_string()),
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b", "c"])).unwrap();
+
+        assert_eq!(page.items, ["b", "c"]);
+        assert_eq!(page.next_key, None);
+        assert_eq!(page.pagination()["next_key"], Value::Null);
+    }
+
+    #[test]
+    fn offset_past_the_end_is_empty() {
+        let request = PageRequest {
+            offset: Some("10".to_string()),
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b"])).unwrap();
+
+        assert!(page.items.is_empty());
+        assert_eq!(page.next_key, None);
+    }
+
+    #[test]
+    fn key_and_offset_conflict() {
+        let request = PageRequest {
+            key: key("a"),
+            offset: Some("1".to_string()),
+            ..Default::default()
+        };
+
+        assert!(matches!(request.paginate(items(&["a"])), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        let request = PageRequest {
+            limit: Some("abc".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(request.paginate(items(&["a"])), Err(Error::InvalidArgument(_))));
+
+        let request = PageRequest {
+            offset: Some("-1".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(request.paginate(items(&["a"])), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn empty_numbers_are_unset() {
+        let request = PageRequest {
+            offset: Some(String::new()),
+            limit: Some(String::new()),
+            ..Default::default()
+        };
+
+        assert_eq!(request.paginate(items(&["a", "b"])).unwrap().items, ["a", "b"]);
+    }
+
+    #[test]
+    fn invalid_key() {
+        let request = PageRequest {
+            key: Some("not base64!".to_string()),
+            ..Default::default()
+        };
+
+        assert!(matches!(request.paginate(items(&["a"])), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn next_key_resumes_the_listing() {
+        let all = items(&["a", "b", "c", "d"]);
+        let first = PageRequest {
+            limit: Some("2".to_string()),
+            ..Default::default()
+        };
+        let page = first.paginate(all.clone()).unwrap();
+        assert_eq!(page.items, ["a", "b"]);
+
+        let second = PageRequest {
+            key: page.next_key.map(base64::encode),
+            limit: Some("2".to_string()),
+            ..Default::default()
+        };
+        let page = second.paginate(all).unwrap();
+
+        assert_eq!(page.items, ["c", "d"]);
+        assert_eq!(page.next_key, None);
+    }
+
+    #[test]
+    fn key_of_a_removed_item_starts_at_the_next_one() {
+        let request = PageRequest {
+            key: key("bb"),
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b", "c", "d"])).unwrap();
+
+        assert_eq!(page.items, ["c", "d"]);
+    }
+
+    #[test]
+    fn key_of_a_removed_item_in_reverse() {
+        let request = PageRequest {
+            key: key("bb"),
+            reverse: true,
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b", "c", "d"])).unwrap();
+
+        assert_eq!(page.items, ["b", "a"]);
+    }
+
+    #[test]
+    fn reverse_with_limit() {
+        let request = PageRequest {
+            limit: Some("2".to_string()),
+            reverse: true,
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b", "c"])).unwrap();
+
+        assert_eq!(page.items, ["c", "b"]);
+        assert_eq!(page.next_key, Some(b"a".to_vec()));
+    }
+
+    #[test]
+    fn key_past_the_end_is_empty() {
+        let request = PageRequest {
+            key: key("z"),
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b"])).unwrap();
+
+        assert!(page.items.is_empty());
+    }
+
+    #[test]
+    fn total_only_when_asked_for() {
+        let request = PageRequest {
+            limit: Some("1".to_string()),
+            count_total: true,
+            ..Default::default()
+        };
+
+        let page = request.paginate(items(&["a", "b", "c"])).unwrap();
+        assert_eq!(page.pagination()["total"], "3");
+
+        let page = PageRequest::default().paginate(items(&["a", "b", "c"])).unwrap();
+        assert_eq!(page.pagination()["total"], "0");
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ipnet::IpNet;
+use rocket::data::Data;
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::response::Responder;
+use rocket::route::{Handler, Outcome, Route};
+
+use crate::config::RateLimitConfig;
+use crate::error::Error;
+
+/// Past this many tracked clients, buckets that have refilled completely are dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per client IP, or per /64 network for IPv6 clients, who usually get a
+/// whole /64 and could otherwise pick a fresh address for every request. Each client may
+/// spend up to `burst` tokens at once, refilled at `rate` tokens per second; a request
+/// costs its route's weight.
+///
+/// Clones share the buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    trusted_proxies: Vec<IpNet>,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+/// The bucket `client` is charged to: its /64 for IPv6, the address itself for IPv4.
+fn bucket_key(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(address) => IpAddr::V4(address),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(address) & !(u64::MAX as u128))),
+        },
+        IpAddr::V4(_) => client,
+    }
+}
+
+fn parse_network(value: &str) -> Result<IpNet, String> {
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("invalid trusted proxy address: {}", value))
+}
+
+impl RateLimiter {
+    /// Returns `None` when rate limiting is disabled.
+    pub fn new(config: &RateLimitConfig) -> Result<Option<Self>, String> {
+        if config.requests_per_second <= 0.0 {
+            return Ok(None);
+        }
+
+        let trusted_proxies = config
+            .trusted_proxies
+            .iter()
+            .map(|value| parse_network(value))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(Self {
+            rate: config.requests_per_second,
+            burst: config.burst.max(1.0),
+            trusted_proxies,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }))
+    }
+
+    fn is_trusted(&self, address: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|network| network.contains(&address))
+    }
+
+    /// The address of the client, looking through `X-Forwarded-For` only when the request
+    /// comes from a trusted proxy. The rightmost untrusted hop is taken, as everything to
+    /// its left could have been set by the client itself.
+    fn client_ip(&self, request: &Request<'_>) -> Option<IpAddr> {
+        let remote = request.remote()?.ip();
+        if !self.is_trusted(remote) {
+            return Some(remote);
+        }
+
+        let forwarded: Vec<IpAddr> = request
+            .headers()
+            .get("X-Forwarded-For")
+            .flat_map(|value| value.split(','))
+            .filter_map(|hop| hop.trim().parse().ok())
+            .collect();
+
+        let client = forwarded
+            .iter()
+            .rev()
+            .find(|&&address| !self.is_trusted(address))
+            .or(forwarded.first())
+            .copied();
+
+        Some(client.unwrap_or(remote))
+    }
+
+    /// Takes `cost` tokens from the client's bucket, or returns how long it has to wait for them.
+    fn acquire(&self, client: IpAddr, cost: f64) -> Result<(), Duration> {
+        let cost = cost.min(self.burst);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(bucket_key(client)).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens < cost {
+            let wait = (cost - bucket.tokens) / self.rate;
+            return Err(Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX));
+        }
+
+        bucket.tokens -= cost;
+        Ok(())
+    }
+}
+
+/// Route handler charging the client before running the wrapped handler.
+#[derive(Clone)]
+struct Limited {
+    cost: f64,
+    handler: Box<dyn Handler>,
+}
+
+#[rocket::async_trait]
+impl Handler for Limited {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let limiter = request.rocket().state::<RateLimiter>();
+        let client = limiter.and_then(|limiter| Some((limiter, limiter.client_ip(request)?)));
+
+        if let Some((limiter, client)) = client {
+            if let Err(wait) = limiter.acquire(client, self.cost) {
+                let error = Error::ResourceExhausted(format!("rate limit exceeded for {}", client));
+
+                return match error.respond_to(request) {
+                    Ok(mut response) => {
+                        let retry_after = wait.as_secs_f64().ceil() as u64;
+                        response.set_header(Header::new("Retry-After", retry_after.to_string()));
+                        Outcome::Success(response)
+                    }
+                    Err(status) => Outcome::Error(status),
+                };
+            }
+        }
+
+        self.handler.handle(request, data).await
+    }
+}
+
+/// Wraps the handlers of `routes` so that each request costs its route's weight from
+/// `config.costs` (1 by default). Routes with a weight of 0 are left untouched.
+pub fn limit(routes: Vec<Route>, config: &RateLimitConfig) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            let cost = route
+                .name
+                .as_ref()
+                .and_then(|name| config.costs.get(name.as_ref()))
+                .copied()
+                .unwrap_or(1.0);
+
+            if cost > 0.0 {
+                route.handler = Box::new(Limited {
+                    cost,
+                    handler: route.handler,
+                });
+            }
+
+            route
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use rocket::local::blocking::Client;
+
+    use super::*;
+
+    fn limiter(rate: f64, burst: f64, trusted_proxies: &[&str]) -> RateLimiter {
+        let config = RateLimitConfig {
+            requests_per_second: rate,
+            burst,
+            trusted_proxies: trusted_proxies.iter().map(|proxy| proxy.to_string()).collect(),
+            ..Default::default()
+        };
+
+        RateLimiter::new(&config).unwrap().unwrap()
+    }
+
+    fn client_ip(limiter: &RateLimiter, remote: &str, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let client = Client::untracked(rocket::build()).unwrap();
+        let mut request = client.get("/").remote(SocketAddr::new(remote.parse().unwrap(), 1234));
+        if let Some(forwarded_for) = forwarded_for {
+            request = request.header(Header::new("X-Forwarded-For", forwarded_for.to_string()));
+        }
+
+        limiter.client_ip(request.inner())
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn disabled_without_a_rate() {
+        assert!(RateLimiter::new(&RateLimitConfig::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_trusted_proxies() {
+        let config = RateLimitConfig {
+            requests_per_second: 1.0,
+            trusted_proxies: vec!["not an address".to_string()],
+            ..Default::default()
+        };
+
+        assert!(RateLimiter::new(&config).is_err());
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let limiter = limiter(1.0, 1.0, &["10.0.0.0/8"]);
+
+        assert_eq!(client_ip(&limiter, "203.0.113.1", Some("198.51.100.7")), ip("203.0.113.1"));
+    }
+
+    #[test]
+    fn takes_the_rightmost_untrusted_hop() {
+        let limiter = limiter(1.0, 1.0, &["10.0.0.0/8", "192.0.2.1"]);
+
+        // The client may have sent a forged first hop.
+        let forwarded_for = "1.1.1.1, 198.51.100.7, 192.0.2.1";
+
+        assert_eq!(client_ip(&limiter, "10.0.0.2", Some(forwarded_for)), ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn falls_back_to_the_first_hop_when_all_are_trusted() {
+        let limiter = limiter(1.0, 1.0, &["10.0.0.0/8"]);
+
+        assert_eq!(client_ip(&limiter, "10.0.0.2", Some("10.1.1.1, 10.2.2.2")), ip("10.1.1.1"));
+    }
+
+    #[test]
+    fn falls_back_to_the_peer_without_forwarded_for() {
+        let limiter = limiter(1.0, 1.0, &["10.0.0.0/8"]);
+
+        assert_eq!(client_ip(&limiter, "10.0.0.2", None), ip("10.0.0.2"));
+        assert_eq!(client_ip(&limiter, "10.0.0.2", Some("garbage")), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn spends_the_burst_then_asks_to_wait() {
+        let limiter = limiter(1.0, 2.0, &[]);
+        let client = "203.0.113.1".parse().unwrap();
+
+        assert!(limiter.acquire(client, 1.0).is_ok());
+        assert!(limiter.acquire(client, 1.0).is_ok());
+
+        let wait = limiter.acquire(client, 1.0).unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn buckets_are_per_client() {
+        let limiter = limiter(1.0, 1.0, &[]);
+
+        assert!(limiter.acquire("203.0.113.1".parse().unwrap(), 1.0).is_ok());
+        assert!(limiter.acquire("203.0.113.1".parse().unwrap(), 1.0).is_err());
+        assert!(limiter.acquire("203.0.113.2".parse().unwrap(), 1.0).is_ok());
+    }
+
+    #[test]
+    fn ipv6_clients_share_their_64() {
+        let limiter = limiter(1.0, 1.0, &[]);
+
+        assert!(limiter.acquire("2001:db8:1:2::1".parse().unwrap(), 1.0).is_ok());
+        assert!(limiter.acquire("2001:db8:1:2:ffff::2".parse().unwrap(), 1.0).is_err());
+        assert!(limiter.acquire("2001:db8:1:3::1".parse().unwrap(), 1.0).is_ok());
+    }
+
+    #[test]
+    fn ipv4_mapped_clients_share_the_ipv4_bucket() {
+        let limiter = limiter(1.0, 1.0, &[]);
+
+        assert!(limiter.acquire("203.0.113.1".parse().unwrap(), 1.0).is_ok());
+        assert!(limiter.acquire("::ffff:203.0.113.1".parse().unwrap(), 1.0).is_err());
+    }
+
+    #[test]
+    fn tiny_rates_do_not_overflow_the_wait() {
+        let limiter = limiter(1e-300, 1.0, &[]);
+        let client = "203.0.113.1".parse().unwrap();
+
+        assert!(limiter.acquire(client, 1.0).is_ok());
+        assert_eq!(limiter.acquire(client, 1.0).unwrap_err(), Duration::MAX);
+    }
+
+    #[test]
+    fn costs_above_the_burst_are_capped() {
+        let limiter = limiter(1.0, 5.0, &[]);
+
+        assert!(limiter.acquire("203.0.113.1".parse().unwrap(), 50.0).is_ok());
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = limiter(100.0, 1.0, &[]);
+        let client = "203.0.113.1".parse().unwrap();
+
+        assert!(limiter.acquire(client, 1.0).is_ok());
+        assert!(limiter.acquire(client, 1.0).is_err());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(limiter.acquire(client, 1.0).is_ok());
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use rocket::figment::Figment;
+use rocket::tokio::{self, time};
+use rocket::Shutdown;
+use tokio_rustls::rustls;
+
+use crate::config::Config;
+use crate::transport::{self, TransportError};
+
+/// Certificate files Rocket was configured with through `tls.certs`, `tls.key` and
+/// `tls.mutual.ca_certs`. Inline PEM data cannot change on disk and is left out.
+fn watched_files(figment: &Figment) -> Vec<String> {
+    ["tls.certs", "tls.key", "tls.mutual.ca_certs"]
+        .into_iter()
+        .filter_map(|key| figment.extract_inner::<String>(key).ok())
+        .filter(|path| Path::new(path).is_file())
+        .collect()
+}
+
+fn modification_times(paths: &[String]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+/// Checks that the certificates can be loaded, so that we do not shut down for a reload
+/// that would fail, e.g. because the files are still being written.
+fn validate(figment: &Figment) -> Result<(), TransportError> {
+    let certs = figment.extract_inner::<String>("tls.certs")?;
+    let key = figment.extract_inner::<String>("tls.key")?;
+
+    let certs = transport::load_certs(&certs)?;
+    if certs.is_empty() {
+        return Err("no certificate found".into());
+    }
+
+    rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, transport::load_key(&key)?)?;
+
+    if let Ok(ca_certs) = figment.extract_inner::<String>("tls.mutual.ca_certs") {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in transport::load_certs(&ca_certs)? {
+            roots.add(&cert)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Longest `shutdown.grace` and `shutdown.mercy`, in seconds, while reloads are enabled.
+const RELOAD_GRACE: u32 = 2;
+
+/// A reload stops the listener until the relaunch binds again, after Rocket's shutdown
+/// grace and mercy periods (2 and 3 seconds by default, often raised for long requests).
+/// Rocket reads them once at ignition and applies them to any shutdown, so while reloads
+/// are enabled we cap both, which also shortens shutdowns on a signal.
+pub fn reload_grace(figment: Figment, config: &Config) -> Figment {
+    if watched_files(&figment).is_empty() || config.tls_reload_interval == 0 {
+        return figment;
+    }
+
+    let cap = |key: &str| figment.extract_inner::<u32>(key).map_or(RELOAD_GRACE, |secs| secs.min(RELOAD_GRACE));
+    let (grace, mercy) = (cap("shutdown.grace"), cap("shutdown.mercy"));
+
+    figment
+        .merge(("shutdown.grace", grace))
+        .merge(("shutdown.mercy", mercy))
+}
+
+/// Rocket 0.5 reads the certificates only when it binds, so to pick up renewed ones
+/// we gracefully shut the server down once they change on disk and launch it again.
+/// Changed certificates that do not load are ignored until they are fixed.
+///
+/// Returns a flag telling whether the shutdown was triggered by a reload.
+pub fn watch(figment: &Figment, config: &Config, shutdown: Shutdown) -> Arc<AtomicBool> {
+    let reloading = Arc::new(AtomicBool::new(false));
+    let paths = watched_files(figment);
+
+    if paths.is_empty() || config.tls_reload_interval == 0 {
+        return reloading;
+    }
+
+    let interval = Duration::from_secs(config.tls_reload_interval);
+    let flag = reloading.clone();
+    let figment = figment.clone();
+
+    tokio::spawn(async move {
+        let initial = modification_times(&paths);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.clone() => return,
+                _ = time::sleep(interval) => {}
+            }
+
+            if modification_times(&paths) == initial {
+                continue;
+            }
+
+            match validate(&figment) {
+                Ok(()) => {
+                    flag.store(true, Ordering::SeqCst);
+                    shutdown.notify();
+                    return;
+                }
+                Err(error) => tracing::warn!(%error, "certificates changed but cannot be loaded, keeping the current ones"),
+            }
+        }
+    });
+
+    reloading
+}
@@ -0,0 +1,143 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use rocket::tokio::{net::TcpStream, time};
+use tokio_rustls::rustls::{
+    self,
+    client::{ServerCertVerified, ServerCertVerifier},
+    ClientConfig, ServerName,
+};
+use tokio_rustls::TlsConnector;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity, Uri};
+
+use crate::config::UpstreamConfig;
+
+pub type TransportError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Builds a lazily-connected channel to `url`, picking the transport from its scheme:
+/// `http://` talks plaintext gRPC, `https://` uses TLS with the configured CA bundle,
+/// client identity and SNI override.
+pub fn connect_lazy(url: &str, config: &UpstreamConfig) -> Result<Channel, TransportError> {
+    let uri: Uri = url.parse()?;
+
+    let endpoint = match uri.scheme_str() {
+        Some("http") => Endpoint::from(uri),
+        Some("https") if config.insecure_skip_verify => {
+            return insecure_channel(uri, config);
+        }
+        Some("https") => Endpoint::from(uri).tls_config(client_tls_config(config)?)?,
+        _ => return Err(format!("unsupported node URL scheme: {}", url).into()),
+    };
+
+    Ok(configure(endpoint, config).connect_lazy())
+}
+
+fn configure(endpoint: Endpoint, config: &UpstreamConfig) -> Endpoint {
+    endpoint
+        .connect_timeout(Duration::from_secs(config.connect_timeout))
+        .tcp_keepalive(Some(Duration::from_secs(config.keepalive_interval)))
+        .http2_keep_alive_interval(Duration::from_secs(config.keepalive_interval))
+        .keep_alive_timeout(Duration::from_secs(config.keepalive_timeout))
+        .keep_alive_while_idle(true)
+}
+
+fn client_tls_config(config: &UpstreamConfig) -> Result<ClientTlsConfig, TransportError> {
+    let mut tls = ClientTlsConfig::new();
+
+    if let Some(path) = &config.ca_cert {
+        tls = tls.ca_certificate(Certificate::from_pem(fs::read(path)?));
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            tls = tls.identity(Identity::from_pem(fs::read(cert)?, fs::read(key)?));
+        }
+        (None, None) => {}
+        _ => return Err("both client_cert and client_key are required for mutual TLS".into()),
+    }
+
+    if let Some(domain) = &config.tls_domain {
+        tls = tls.domain_name(domain);
+    }
+
+    Ok(tls)
+}
+
+/// Accepts any server certificate. Only meant for testing against nodes with self-signed certificates.
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// tonic cannot skip certificate verification, so in that mode the TLS handshake is done by
+/// our own connector. The endpoint itself is plaintext so that tonic does not wrap the stream
+/// in TLS a second time, while requests keep the original `https` origin.
+fn insecure_channel(uri: Uri, config: &UpstreamConfig) -> Result<Channel, TransportError> {
+    let authority = uri.authority().ok_or("node URL has no host")?.clone();
+    let host = authority.host().to_string();
+    let port = authority.port_u16().unwrap_or(443);
+    let domain = config.tls_domain.clone().unwrap_or_else(|| host.clone());
+    let server_name = ServerName::try_from(domain.as_str())?;
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(NoVerification));
+
+    let mut tls_config = match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err("both client_cert and client_key are required for mutual TLS".into()),
+    };
+    tls_config.alpn_protocols = vec![b"h2".to_vec()];
+
+    let connector = TlsConnector::from(Arc::new(tls_config));
+    let connect_timeout = Duration::from_secs(config.connect_timeout);
+    let service = tower::service_fn(move |_: Uri| {
+        let connector = connector.clone();
+        let server_name = server_name.clone();
+        let address = (host.clone(), port);
+
+        async move {
+            let tcp = time::timeout(connect_timeout, TcpStream::connect(address)).await??;
+            tcp.set_nodelay(true)?;
+
+            connector.connect(server_name, tcp).await
+        }
+    });
+
+    let endpoint = Endpoint::from_shared(format!("http://{}", authority))?.origin(uri);
+
+    Ok(configure(endpoint, config).connect_with_connector_lazy(service))
+}
+
+pub fn load_certs(path: &str) -> Result<Vec<rustls::Certificate>, TransportError> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+
+    Ok(certs.into_iter().map(rustls::Certificate).collect())
+}
+
+pub fn load_key(path: &str) -> Result<rustls::PrivateKey, TransportError> {
+    for item in rustls_pemfile::read_all(&mut BufReader::new(File::open(path)?))? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(rustls::PrivateKey(key)),
+            _ => {}
+        }
+    }
+
+    Err(format!("no private key found in {}", path).into())
+}
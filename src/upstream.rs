@@ -0,0 +1,468 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use penumbra_proto::{
+    core::app::v1::query_service_client::QueryServiceClient as AppQueryServiceClient,
+    core::component::governance::v1::query_service_client::QueryServiceClient as GovernanceQueryServiceClient,
+    core::component::sct::v1::query_service_client::QueryServiceClient as SctQueryServiceClient,
+    core::component::shielded_pool::v1::query_service_client::QueryServiceClient as ShieldedPoolQueryServiceClient,
+    core::component::stake::v1::query_service_client::QueryServiceClient as StakeQueryServiceClient,
+    util::tendermint_proxy::v1::{
+        tendermint_proxy_service_client::TendermintProxyServiceClient,
+        GetStatusRequest,
+    },
+};
+use rocket::data::Data;
+use rocket::fairing::AdHoc;
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::route::{Handler, Outcome, Route};
+use rocket::tokio::{self, sync::Semaphore, time};
+use rand::Rng;
+use tonic::transport::Channel;
+use tonic::Code;
+
+use crate::breaker::CircuitBreaker;
+use crate::config::UpstreamConfig;
+use crate::error::Error;
+use crate::metrics::{self, MeteredChannel};
+use crate::transport::{self, TransportError};
+
+/// Last observed state of an upstream node.
+#[derive(Clone, Debug, Default)]
+pub struct Health {
+    pub reachable: bool,
+    pub catching_up: bool,
+    pub latest_block_height: u64,
+    /// Unix time of the latest block, in seconds.
+    pub latest_block_time: Option<i64>,
+}
+
+impl Health {
+    fn is_usable(&self) -> bool {
+        self.reachable && !self.catching_up
+    }
+}
+
+/// A single pd node we can forward queries to.
+pub struct Node {
+    pub url: String,
+    channel: Channel,
+    health: RwLock<Health>,
+    breaker: CircuitBreaker,
+}
+
+impl Node {
+    pub fn health(&self) -> Health {
+        self.health.read().unwrap().clone()
+    }
+
+    async fn check(&self, timeout: Duration) {
+        let mut client = TendermintProxyServiceClient::new(self.channel.clone());
+        let status = time::timeout(timeout, client.get_status(GetStatusRequest {})).await;
+
+        let health = match status {
+            Ok(Ok(response)) => match response.into_inner().sync_info {
+                Some(sync_info) => Health {
+                    reachable: true,
+                    catching_up: sync_info.catching_up,
+                    latest_block_height: sync_info.latest_block_height,
+                    latest_block_time: sync_info.latest_block_time.map(|time| time.seconds),
+                },
+                None => Health {
+                    reachable: true,
+                    catching_up: true,
+                    latest_block_height: 0,
+                    latest_block_time: None,
+                },
+            },
+            _ => Health {
+                reachable: false,
+                ..self.health()
+            },
+        };
+
+        metrics::record_node_health(&self.url, health.is_usable(), health.latest_block_height);
+        *self.health.write().unwrap() = health;
+    }
+}
+
+tokio::task_local! {
+    /// URL of the last node that answered a query for the request being handled.
+    static SERVED_BY: RefCell<Option<String>>;
+}
+
+/// The node that answered the request's upstream queries, if it made any.
+struct ServedBy(Option<String>);
+
+/// Metered channel to the current node.
+pub type UpstreamChannel = MeteredChannel<Channel>;
+
+struct Inner {
+    nodes: Vec<Node>,
+    // Held by `call` for a whole query, streamed responses included.
+    in_flight: Semaphore,
+    selected: AtomicUsize,
+    health_check_interval: Duration,
+    max_height_lag: u64,
+    call_timeout: Duration,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+/// Connections to the upstream pd nodes, shared by all the handlers.
+///
+/// Every node gets its own channel, plaintext or TLS depending on its URL scheme,
+/// which is connected lazily on first use and transparently reconnects if the
+/// connection drops. Queries are routed to the
+/// node picked by the last health check, so cloning clients out of it is cheap.
+#[derive(Clone)]
+pub struct Upstream {
+    inner: Arc<Inner>,
+    // Set on the clones handed to `call` queries, so that they stick to the node picked for them.
+    node: Option<usize>,
+}
+
+impl Upstream {
+    pub fn new(config: &UpstreamConfig) -> Result<Self, TransportError> {
+        let nodes = config
+            .nodes
+            .iter()
+            .map(|url| {
+                Ok(Node {
+                    url: url.to_string(),
+                    channel: transport::connect_lazy(url, config)?,
+                    health: RwLock::new(Health::default()),
+                    breaker: CircuitBreaker::new(
+                        config.breaker_threshold,
+                        Duration::from_secs(config.breaker_cooldown),
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                nodes,
+                in_flight: Semaphore::new(match config.max_in_flight {
+                    0 => Semaphore::MAX_PERMITS,
+                    max => max,
+                }),
+                selected: AtomicUsize::new(0),
+                health_check_interval: Duration::from_secs(config.health_check_interval),
+                max_height_lag: config.max_height_lag,
+                call_timeout: Duration::from_secs(config.call_timeout),
+                retries: config.retries,
+                retry_backoff: Duration::from_millis(config.retry_backoff),
+            }),
+            node: None,
+        })
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.inner.nodes
+    }
+
+    /// The node queries are currently routed to.
+    pub fn current(&self) -> &Node {
+        let index = self.node.unwrap_or_else(|| self.inner.selected.load(Ordering::Relaxed));
+        &self.inner.nodes[index]
+    }
+
+    /// Picks the node for the next call: the selected one unless its circuit breaker is
+    /// open, in which case the next usable node whose breaker lets the call through.
+    fn pick_node(&self) -> Option<usize> {
+        let selected = self.inner.selected.load(Ordering::Relaxed);
+        let count = self.inner.nodes.len();
+
+        (0..count)
+            .map(|offset| (selected + offset) % count)
+            .filter(|&index| index == selected || self.inner.nodes[index].health().is_usable())
+            .find(|&index| self.inner.nodes[index].breaker.allow())
+    }
+
+    fn channel(&self) -> UpstreamChannel {
+        MeteredChannel::new(self.current().channel.clone())
+    }
+
+    pub fn app(&self) -> AppQueryServiceClient<UpstreamChannel> {
+        AppQueryServiceClient::new(self.channel())
+    }
+
+    pub fn stake(&self) -> StakeQueryServiceClient<UpstreamChannel> {
+        StakeQueryServiceClient::new(self.channel())
+    }
+
+    pub fn governance(&self) -> GovernanceQueryServiceClient<UpstreamChannel> {
+        GovernanceQueryServiceClient::new(self.channel())
+    }
+
+    pub fn sct(&self) -> SctQueryServiceClient<UpstreamChannel> {
+        SctQueryServiceClient::new(self.channel())
+    }
+
+    pub fn shielded_pool(&self) -> ShieldedPoolQueryServiceClient<UpstreamChannel> {
+        ShieldedPoolQueryServiceClient::new(self.channel())
+    }
+
+    pub fn tendermint(&self) -> TendermintProxyServiceClient<UpstreamChannel> {
+        TendermintProxyServiceClient::new(self.channel())
+    }
+
+    /// Runs `query` against the current node within the per-call deadline, which covers
+    /// reading streamed responses to the end as long as `query` collects them. Queries
+    /// wait for a slot when `max_in_flight` of them are already running, and fail with
+    /// `ResourceExhausted` if none frees up within the deadline.
+    ///
+    /// Nodes failing with `Unavailable`, `DeadlineExceeded` or `ResourceExhausted` too many
+    /// times in a row have their circuit breaker opened, and calls fail over to the next
+    /// usable node until it closes again. All the queries are read-only, so attempts failing
+    /// with `Unavailable` or `DeadlineExceeded` are retried after a jittered exponential
+    /// backoff, each time on the node picked then.
+    pub async fn call<T, F, Fut>(&self, mut query: F) -> Result<T, Error>
+    where
+        F: FnMut(Upstream) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            // Waiting for a slot is our own doing, so it gets its own deadline and neither
+            // counts against the node nor is retried. The slot is held until the response is
+            // fully read, which is the expensive part of the streaming queries.
+            let permit = match time::timeout(self.inner.call_timeout, self.inner.in_flight.acquire()).await {
+                Ok(permit) => permit.expect("the semaphore is never closed"),
+                Err(_) => {
+                    return Err(Error::ResourceExhausted(
+                        "too many upstream calls in flight, try again later".to_string(),
+                    ));
+                }
+            };
+
+            let Some(index) = self.pick_node() else {
+                return Err(tonic::Status::unavailable(
+                    "every upstream node is failing, not sending queries to them for now",
+                )
+                .into());
+            };
+            let node = &self.inner.nodes[index];
+            let upstream = Upstream {
+                inner: self.inner.clone(),
+                node: Some(index),
+            };
+
+            let result = match time::timeout(self.inner.call_timeout, query(upstream)).await {
+                Ok(result) => result,
+                Err(_) => Err(tonic::Status::deadline_exceeded("upstream call timed out").into()),
+            };
+
+            // Background cache refreshes run outside of any request and have nowhere to record it.
+            let _ = SERVED_BY.try_with(|served_by| *served_by.borrow_mut() = Some(node.url.clone()));
+
+            let code = match &result {
+                Ok(_) => None,
+                Err(Error::Upstream(status)) => Some(status.code()),
+                Err(_) => None,
+            };
+
+            // Other errors, `Unknown` and `Internal` included, come from pd handling the query,
+            // e.g. looking up a proposal that does not exist, and say nothing about the node.
+            match code {
+                Some(Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted) => {
+                    node.breaker.record_failure();
+                }
+                _ => node.breaker.record_success(),
+            }
+
+            let retryable = matches!(code, Some(Code::Unavailable | Code::DeadlineExceeded));
+            if !retryable || attempt >= self.inner.retries {
+                return result;
+            }
+
+            attempt += 1;
+            let backoff = self.inner.retry_backoff * 2u32.saturating_pow(attempt - 1);
+            let delay = backoff.mul_f64(rand::thread_rng().gen_range(0.5..1.0));
+            tracing::warn!(attempt, delay_ms = delay.as_millis() as u64, code = ?code, "retrying upstream call");
+
+            drop(permit);
+            time::sleep(delay).await;
+        }
+    }
+
+    /// Checks every node and routes further queries to the most synced usable one.
+    pub async fn check_health(&self) {
+        let timeout = self.inner.health_check_interval;
+        futures::future::join_all(self.nodes().iter().map(|node| node.check(timeout))).await;
+
+        let health: Vec<Health> = self.nodes().iter().map(Node::health).collect();
+        let selected = self.inner.selected.load(Ordering::Relaxed);
+
+        // Nodes whose circuit breaker is open fail every query, however synced they look.
+        let usable = |index: usize| health[index].is_usable() && !self.nodes()[index].breaker.is_open();
+
+        // Nodes that are up and synced, and not too far behind the best of them,
+        // are all good enough; stick to the current one if possible to avoid flapping.
+        let best_height = (0..health.len())
+            .filter(|&index| usable(index))
+            .map(|index| health[index].latest_block_height)
+            .max();
+
+        let candidates: Vec<usize> = match best_height {
+            Some(best_height) => (0..health.len())
+                .filter(|&index| usable(index))
+                .filter(|&index| {
+                    health[index].latest_block_height + self.inner.max_height_lag >= best_height
+                })
+                .collect(),
+            // Nothing is synced, so fall back to whatever answers and is the furthest ahead.
+            None => (0..health.len())
+                .filter(|&index| health[index].reachable && !self.nodes()[index].breaker.is_open())
+                .max_by_key(|&index| health[index].latest_block_height)
+                .into_iter()
+                .collect(),
+        };
+
+        if candidates.contains(&selected) {
+            return;
+        }
+
+        if let Some(&index) = candidates.first() {
+            self.inner.selected.store(index, Ordering::Relaxed);
+        }
+    }
+
+    /// Periodically health-checks the nodes in the background once Rocket has launched,
+    /// and reports the node used for each request tracked by [`track`] in the
+    /// `X-Upstream-Node` header.
+    pub fn fairing() -> AdHoc {
+        AdHoc::on_ignite("Upstream", |rocket| async {
+            rocket
+                .attach(AdHoc::on_liftoff("Upstream health checks", |rocket| Box::pin(async move {
+                    let upstream = rocket.state::<Upstream>().unwrap().clone();
+                    let shutdown = rocket.shutdown();
+
+                    tokio::spawn(async move {
+                        let mut interval = time::interval(upstream.inner.health_check_interval);
+                        loop {
+                            tokio::select! {
+                                _ = shutdown.clone() => return,
+                                _ = interval.tick() => upstream.check_health().await,
+                            }
+                        }
+                    });
+                })))
+                .attach(AdHoc::on_response("Upstream node header", |request, response| Box::pin(async move {
+                    if let ServedBy(Some(url)) = request.local_cache(|| ServedBy(None)) {
+                        response.set_header(Header::new("X-Upstream-Node", url.clone()));
+                    }
+                })))
+        })
+    }
+}
+
+/// Route handler keeping track of the node that served the wrapped handler's upstream queries.
+#[derive(Clone)]
+struct Tracked {
+    handler: Box<dyn Handler>,
+}
+
+#[rocket::async_trait]
+impl Handler for Tracked {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let (outcome, served_by) = SERVED_BY
+            .scope(RefCell::new(None), async {
+                let outcome = self.handler.handle(request, data).await;
+                (outcome, SERVED_BY.with(|served_by| served_by.take()))
+            })
+            .await;
+
+        request.local_cache(|| ServedBy(served_by));
+        outcome
+    }
+}
+
+/// Wraps the handlers of `routes` so that the `X-Upstream-Node` header reports the node
+/// that actually answered, and is left out when the response came from the cache.
+pub fn track(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Tracked {
+                handler: route.handler,
+            });
+            route
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+
+    /// A single node nothing listens on; the queries below never touch it.
+    fn upstream(max_in_flight: usize) -> Upstream {
+        let config = UpstreamConfig {
+            nodes: vec!["http://127.0.0.1:1".to_string()],
+            max_in_flight,
+            call_timeout: 1,
+            retries: 2,
+            retry_backoff: 1,
+            breaker_threshold: 1,
+            ..Default::default()
+        };
+
+        Upstream::new(&config).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn waiting_for_a_slot_does_not_count_against_the_node() {
+        let upstream = upstream(1);
+        let _busy = upstream.inner.in_flight.acquire().await.unwrap();
+        let queries = AtomicU32::new(0);
+
+        let result = upstream
+            .call(|_| {
+                queries.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::ResourceExhausted(_))));
+        assert_eq!(queries.load(Ordering::SeqCst), 0);
+        assert!(!upstream.nodes()[0].breaker.is_open());
+    }
+
+    #[rocket::async_test]
+    async fn slow_nodes_open_the_breaker() {
+        let upstream = upstream(1);
+
+        let result: Result<(), Error> = upstream
+            .call(|_| async {
+                time::sleep(Duration::from_secs(2)).await;
+                Ok(())
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::Upstream(_))));
+        assert!(upstream.nodes()[0].breaker.is_open());
+    }
+
+    #[rocket::async_test]
+    async fn application_errors_do_not_open_the_breaker() {
+        let upstream = upstream(0);
+
+        for _ in 0..3 {
+            let result: Result<(), Error> = upstream
+                .call(|_| async { Err(tonic::Status::internal("proposal 42 not found").into()) })
+                .await;
+            assert!(result.is_err());
+        }
+
+        assert!(!upstream.nodes()[0].breaker.is_open());
+    }
+}